@@ -0,0 +1,42 @@
+//! Compare naive and `memchr` newline counting side by side with
+//! `cargo bench --features memchr`. The `line_col` and `line_count` benches measure whichever
+//! strategy the crate was built with.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use span::Span;
+
+fn source() -> String {
+    "fn main() {\n    println!(\"hello\");\n}\n".repeat(10_000)
+}
+
+fn count_newlines(c: &mut Criterion) {
+    let source = source();
+    let mut group = c.benchmark_group("count_newlines");
+
+    group.bench_function("naive", |b| {
+        b.iter(|| {
+            black_box(source.as_bytes())
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count()
+        })
+    });
+    #[cfg(feature = "memchr")]
+    group.bench_function("memchr", |b| {
+        b.iter(|| memchr::memchr_iter(b'\n', black_box(source.as_bytes())).count())
+    });
+    group.finish();
+}
+
+fn line_col(c: &mut Criterion) {
+    let source = source();
+    let span = Span::new(source.as_str(), source.len() - 2, source.len());
+
+    c.bench_function("line_col", |b| b.iter(|| black_box(span).line_col()));
+    c.bench_function("line_count", |b| {
+        b.iter(|| black_box(Span::from(source.as_str())).line_count())
+    });
+}
+
+criterion_group!(benches, count_newlines, line_col);
+criterion_main!(benches);
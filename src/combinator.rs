@@ -0,0 +1,1375 @@
+//! Parser combinators that report the spans their inner parsers consume.
+
+// Pairing outputs with spans makes for long `IResult` types throughout.
+#![allow(clippy::type_complexity)]
+
+use crate::{error::SpanError, Span};
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, take_while, take_while1},
+    character::complete::{char, digit1},
+    combinator::{consumed, opt, recognize, value},
+    error::{ErrorKind, ParseError},
+    sequence::{delimited, pair},
+    Err, IResult, InputLength, InputTake, Offset, Parser, Slice,
+};
+use std::cell::Cell;
+
+/// Runs `f` without consuming input, returning the span `f` would have consumed.
+pub fn peek_span<T, O, E, F>(mut f: F) -> impl FnMut(Span<T>) -> IResult<Span<T>, Span<T>, E>
+where
+    T: Clone,
+    E: ParseError<Span<T>>,
+    F: Parser<Span<T>, O, E>,
+{
+    move |input: Span<T>| {
+        let (rest, _) = f.parse(input.clone())?;
+        Ok((input.clone(), Span::between(input, rest)))
+    }
+}
+
+/// Checks that the input starts with `pat` without consuming it, returning the span `pat`
+/// covers.
+pub fn peek_is<'a, 'p, E>(
+    pat: &'p str,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E> + 'p
+where
+    E: ParseError<Span<&'a str>> + 'p,
+    'a: 'p,
+{
+    peek_span(nom::bytes::complete::tag(pat))
+}
+
+/// Maps the output of `parser` with `g`, which also receives the span `parser` consumed.
+pub fn map_spanned<T, O, O2, E, F, G>(
+    mut parser: F,
+    g: G,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, O2, E>
+where
+    T: Clone,
+    F: Parser<Span<T>, O, E>,
+    G: Fn(Span<T>, O) -> O2,
+{
+    move |input: Span<T>| {
+        let (rest, output) = parser.parse(input.clone())?;
+        let span = Span::between(input, rest.clone());
+        Ok((rest, g(span, output)))
+    }
+}
+
+/// Like `nom::combinator::value`, but also returns the span `f` consumed, e.g. to map a
+/// keyword to an enum variant while keeping its location.
+pub fn value_spanned<T, O, V, E, F>(
+    v: V,
+    f: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (Span<T>, V), E>
+where
+    T: Clone,
+    V: Clone,
+    F: Parser<Span<T>, O, E>,
+{
+    map_spanned(f, move |span, _| (span, v.clone()))
+}
+
+/// Runs `f` and, if it succeeds, takes the next id from `counter` for its output, e.g. to
+/// index nodes in an arena. Ids are only used up on success, so nodes are numbered in the order
+/// they finish parsing: children before their parents.
+pub fn with_id<'c, T, O, E, F>(
+    counter: &'c Cell<u32>,
+    f: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (u32, Span<T>, O), E> + 'c
+where
+    T: Clone + 'c,
+    O: 'c,
+    E: 'c,
+    F: Parser<Span<T>, O, E> + 'c,
+{
+    map_spanned(f, move |span, output| {
+        let id = counter.get();
+        counter.set(id + 1);
+        (id, span, output)
+    })
+}
+
+/// Tuples of parsers run in sequence by [`tuple_spanned`].
+pub trait TupleSpanned<T, O, E> {
+    fn parse_spanned(&mut self, input: Span<T>) -> IResult<Span<T>, O, E>;
+}
+
+macro_rules! impl_tuple_spanned {
+    ($($f:ident $o:ident),+) => {
+        impl<T, E, $($o,)+ $($f,)+> TupleSpanned<T, ($((Span<T>, $o),)+), E> for ($($f,)+)
+        where
+            T: Clone,
+            $($f: Parser<Span<T>, $o, E>,)+
+        {
+            #[allow(non_snake_case)]
+            fn parse_spanned(
+                &mut self,
+                input: Span<T>,
+            ) -> IResult<Span<T>, ($((Span<T>, $o),)+), E> {
+                let ($($f,)+) = self;
+                let rest = input;
+                $(
+                    let (next, $o) = $f.parse(rest.clone())?;
+                    let $o = (Span::between(rest, next.clone()), $o);
+                    let rest = next;
+                )+
+                Ok((rest, ($($o,)+)))
+            }
+        }
+    };
+}
+
+impl_tuple_spanned!(F1 O1);
+impl_tuple_spanned!(F1 O1, F2 O2);
+impl_tuple_spanned!(F1 O1, F2 O2, F3 O3);
+impl_tuple_spanned!(F1 O1, F2 O2, F3 O3, F4 O4);
+impl_tuple_spanned!(F1 O1, F2 O2, F3 O3, F4 O4, F5 O5);
+impl_tuple_spanned!(F1 O1, F2 O2, F3 O3, F4 O4, F5 O5, F6 O6);
+
+/// Like `nom::sequence::tuple`, but pairs each parser's output with the span it consumed. Takes
+/// tuples of up to 6 parsers.
+pub fn tuple_spanned<T, O, E, L>(mut parsers: L) -> impl FnMut(Span<T>) -> IResult<Span<T>, O, E>
+where
+    L: TupleSpanned<T, O, E>,
+{
+    move |input: Span<T>| parsers.parse_spanned(input)
+}
+
+/// Like `nom::sequence::separated_pair`, but pairs each element with the span it consumed. The
+/// separator's span is dropped along with its output.
+pub fn separated_pair_spanned<T, O1, O2, O3, E, F, G, H>(
+    first: F,
+    sep: G,
+    second: H,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, ((Span<T>, O1), (Span<T>, O3)), E>
+where
+    T: Clone,
+    F: Parser<Span<T>, O1, E>,
+    G: Parser<Span<T>, O2, E>,
+    H: Parser<Span<T>, O3, E>,
+{
+    let mut parsers = tuple_spanned((first, sep, second));
+    move |input: Span<T>| {
+        let (rest, (first, _, second)) = parsers(input)?;
+        Ok((rest, (first, second)))
+    }
+}
+
+/// Like `nom::sequence::terminated`, but also returns the span of `first` alone, leaving a
+/// trailing delimiter out of it.
+pub fn terminated_span<T, O1, O2, E, F, G>(
+    first: F,
+    mut second: G,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (Span<T>, O1), E>
+where
+    T: Clone,
+    F: Parser<Span<T>, O1, E>,
+    G: Parser<Span<T>, O2, E>,
+{
+    let mut first = map_spanned(first, |span, output| (span, output));
+    move |input: Span<T>| {
+        let (rest, output) = first(input)?;
+        let (rest, _) = second.parse(rest)?;
+        Ok((rest, output))
+    }
+}
+
+/// Like `nom::sequence::preceded`, but also returns the span of `second` alone, leaving a
+/// leading delimiter out of it.
+pub fn preceded_span<T, O1, O2, E, F, G>(
+    mut first: F,
+    second: G,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (Span<T>, O2), E>
+where
+    T: Clone,
+    F: Parser<Span<T>, O1, E>,
+    G: Parser<Span<T>, O2, E>,
+{
+    let mut second = map_spanned(second, |span, output| (span, output));
+    move |input: Span<T>| {
+        let (rest, _) = first.parse(input)?;
+        second(rest)
+    }
+}
+
+/// Like `nom::combinator::opt`, but also returns the span consumed when `f` matches.
+pub fn opt_span<T, O, E, F>(
+    mut f: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, Option<(Span<T>, O)>, E>
+where
+    T: Clone,
+    E: ParseError<Span<T>>,
+    F: Parser<Span<T>, O, E>,
+{
+    move |input: Span<T>| match f.parse(input.clone()) {
+        Ok((rest, output)) => Ok((rest.clone(), Some((Span::between(input, rest), output)))),
+        Err(Err::Error(_)) => Ok((input, None)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like `nom::multi::fold_many0`, but `g` also receives the span of each item, and the span
+/// covering all the items is returned alongside the accumulator.
+pub fn fold_many0_spanned<T, O, E, F, G, H, R>(
+    mut f: F,
+    mut init: H,
+    mut g: G,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (Span<T>, R), E>
+where
+    T: Clone + InputLength,
+    E: ParseError<Span<T>>,
+    F: Parser<Span<T>, O, E>,
+    G: FnMut(R, O, Span<T>) -> R,
+    H: FnMut() -> R,
+{
+    move |input: Span<T>| {
+        let mut acc = init();
+        let mut rest = input.clone();
+        loop {
+            match f.parse(rest.clone()) {
+                Ok((next, item)) => {
+                    if next.input_len() == rest.input_len() {
+                        return Err(Err::Error(E::from_error_kind(rest, ErrorKind::Many0)));
+                    }
+                    acc = g(acc, item, Span::between(rest, next.clone()));
+                    rest = next;
+                }
+                Err(Err::Error(_)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((rest.clone(), (Span::between(input, rest), acc)))
+    }
+}
+
+/// Parses `item (sep item)* sep?`, a list of at least one item allowing a trailing separator.
+/// Returns the span of the whole list, each item with its span, and whether there was a
+/// trailing separator.
+pub fn separated_list_trailing_spanned<T, O, O2, E, F, G>(
+    mut sep: G,
+    mut item: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, (Span<T>, Vec<(Span<T>, O)>, bool), E>
+where
+    T: Clone + InputLength,
+    E: ParseError<Span<T>>,
+    F: Parser<Span<T>, O, E>,
+    G: Parser<Span<T>, O2, E>,
+{
+    move |input: Span<T>| {
+        let (mut rest, first) = item.parse(input.clone())?;
+        let mut items = vec![(Span::between(input.clone(), rest.clone()), first)];
+        let mut trailing = false;
+        loop {
+            let after_sep = match sep.parse(rest.clone()) {
+                Ok((next, _)) => next,
+                Err(Err::Error(_)) => break,
+                Err(e) => return Err(e),
+            };
+            match item.parse(after_sep.clone()) {
+                Ok((next, output)) => {
+                    if next.input_len() == rest.input_len() {
+                        return Err(Err::Error(E::from_error_kind(
+                            rest,
+                            ErrorKind::SeparatedList,
+                        )));
+                    }
+                    items.push((Span::between(after_sep, next.clone()), output));
+                    rest = next;
+                }
+                Err(Err::Error(_)) => {
+                    trailing = true;
+                    rest = after_sep;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((rest.clone(), (Span::between(input, rest), items, trailing)))
+    }
+}
+
+/// Like `nom::multi::many_till`, but also returns the span covering all of `f`'s matches and
+/// the span of `g`'s terminator.
+pub fn many_till_span<T, O, P, E, F, G>(
+    mut f: F,
+    mut g: G,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, ((Span<T>, Vec<O>), (Span<T>, P)), E>
+where
+    T: Clone + InputLength,
+    E: ParseError<Span<T>>,
+    F: Parser<Span<T>, O, E>,
+    G: Parser<Span<T>, P, E>,
+{
+    move |input: Span<T>| {
+        let mut items = Vec::new();
+        let mut rest = input.clone();
+        loop {
+            match g.parse(rest.clone()) {
+                Ok((next, end)) => {
+                    let items = (Span::between(input, rest.clone()), items);
+                    let end = (Span::between(rest, next.clone()), end);
+                    return Ok((next, (items, end)));
+                }
+                Err(Err::Error(_)) => match f.parse(rest.clone()) {
+                    Ok((next, item)) => {
+                        if next.input_len() == rest.input_len() {
+                            return Err(Err::Error(E::from_error_kind(rest, ErrorKind::ManyTill)));
+                        }
+                        items.push(item);
+                        rest = next;
+                    }
+                    Err(Err::Error(e)) => {
+                        return Err(Err::Error(E::append(rest, ErrorKind::ManyTill, e)));
+                    }
+                    Err(e) => return Err(e),
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Lazily applies a parser repeatedly, yielding each output with the span it consumed. Stops at
+/// the first error, or if the parser stops consuming input; [`SpannedIter::remaining`] is then
+/// the input it stopped at.
+pub struct SpannedIter<T, F> {
+    parser: F,
+    input: Span<T>,
+    done: bool,
+}
+
+impl<T, F> SpannedIter<T, F> {
+    pub fn new(input: Span<T>, parser: F) -> Self {
+        Self {
+            parser,
+            input,
+            done: false,
+        }
+    }
+
+    /// The input not yet consumed.
+    pub fn remaining(&self) -> &Span<T> {
+        &self.input
+    }
+}
+
+impl<T, O, E, F> Iterator for SpannedIter<T, F>
+where
+    T: Clone + InputLength,
+    F: FnMut(Span<T>) -> IResult<Span<T>, O, E>,
+{
+    type Item = (Span<T>, O);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match (self.parser)(self.input.clone()) {
+            Ok((rest, output)) if rest.input_len() < self.input.input_len() => {
+                let span = Span::between(std::mem::replace(&mut self.input, rest.clone()), rest);
+                Some((span, output))
+            }
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+/// Like `nom::combinator::cut`, but the resulting failure points at the input where `f` was
+/// committed to rather than wherever `f` gave up.
+pub fn cut_span<T, O, F>(mut f: F) -> impl FnMut(Span<T>) -> IResult<Span<T>, O, SpanError<T>>
+where
+    T: Clone,
+    F: Parser<Span<T>, O, SpanError<T>>,
+{
+    move |input: Span<T>| match f.parse(input.clone()) {
+        Err(Err::Error(e)) => Err(Err::Failure(SpanError { span: input, ..e })),
+        res => res,
+    }
+}
+
+/// Runs `f`, moving the span of any error it returns back to where `f` started. Use it around
+/// constructs that can fail partway through, to report "expected X here" at the start of X.
+pub fn atomic<T, O, F>(mut f: F) -> impl FnMut(Span<T>) -> IResult<Span<T>, O, SpanError<T>>
+where
+    T: Clone,
+    F: Parser<Span<T>, O, SpanError<T>>,
+{
+    move |input: Span<T>| {
+        f.parse(input.clone()).map_err(|e| {
+            e.map(|e| SpanError {
+                span: input.clone(),
+                ..e
+            })
+        })
+    }
+}
+
+/// Runs `f`, replacing any error it returns with one at the entry input carrying `msg` as its
+/// context, e.g. "expected an identifier". The original error is dropped, so the kind is always
+/// `ErrorKind::Fail`.
+pub fn label<T, O, E, F>(
+    msg: &'static str,
+    mut f: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, O, SpanError<T>>
+where
+    T: Clone,
+    F: Parser<Span<T>, O, E>,
+{
+    move |input: Span<T>| {
+        f.parse(input.clone()).map_err(|e| {
+            e.map(|_| SpanError {
+                span: input.clone(),
+                kind: ErrorKind::Fail,
+                stack: Vec::new(),
+                context: vec![(input.clone(), msg)],
+            })
+        })
+    }
+}
+
+/// Like `nom::combinator::all_consuming`. If `f` leaves input over, the error's span is the
+/// leftover input.
+pub fn all_consuming_span<T, O, F>(
+    mut f: F,
+) -> impl FnMut(Span<T>) -> IResult<Span<T>, O, SpanError<T>>
+where
+    T: Clone + InputLength,
+    F: Parser<Span<T>, O, SpanError<T>>,
+{
+    move |input: Span<T>| {
+        let (rest, output) = f.parse(input)?;
+        if rest.input_len() == 0 {
+            Ok((rest, output))
+        } else {
+            Err(Err::Error(SpanError::from_error_kind(rest, ErrorKind::Eof)))
+        }
+    }
+}
+
+/// Consumes a group opened by `open` and closed by the matching `close`, nested to any depth,
+/// returning its span with the delimiters. If the group is never closed the error points at
+/// the innermost unmatched `open`.
+pub fn balanced<'a>(
+    open: char,
+    close: char,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, SpanError<&'a str>> {
+    move |input: Span<&'a str>| {
+        let text = input.as_inner();
+        if !text.starts_with(open) {
+            return Err(Err::Error(SpanError::from_error_kind(
+                input,
+                ErrorKind::Char,
+            )));
+        }
+        let mut opened = Vec::new();
+        for (i, c) in text.char_indices() {
+            if c == open {
+                opened.push(i);
+            } else if c == close {
+                opened.pop();
+                if opened.is_empty() {
+                    return Ok(input.take_split(i + close.len_utf8()));
+                }
+            }
+        }
+        let unmatched = opened.last().copied().unwrap_or_default();
+        let unmatched = input.slice(unmatched..unmatched + open.len_utf8());
+        Err(Err::Error(SpanError::from_error_kind(
+            unmatched,
+            ErrorKind::Eof,
+        )))
+    }
+}
+
+/// Skips ahead to the first `start` marker and consumes through the first `end` after it,
+/// returning the span strictly between the markers and the span including them. Unlike
+/// [`balanced`], markers don't nest.
+pub fn between_markers<'a, 'p, E>(
+    start: &'p str,
+    end: &'p str,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, (Span<&'a str>, Span<&'a str>), E> + 'p
+where
+    E: ParseError<Span<&'a str>> + 'p,
+    'a: 'p,
+{
+    move |input: Span<&'a str>| {
+        let not_found =
+            |at: Span<&'a str>| Err(Err::Error(E::from_error_kind(at, ErrorKind::TakeUntil)));
+        let Some(open) = input.as_inner().find(start) else {
+            return not_found(input);
+        };
+        let contents = input.slice(open + start.len()..);
+        let Some(close) = contents.as_inner().find(end) else {
+            return not_found(contents);
+        };
+        let inner = contents.slice(..close);
+        let rest = contents.slice(close + end.len()..);
+        Ok((rest, (inner, Span::between(input.slice(open..), rest))))
+    }
+}
+
+/// Runs a plain `&str` parser on the text of a span, mapping the remaining input and any error
+/// position back to spans. Eases migrating existing parsers one at a time.
+pub fn lift<'a, O, E, F>(mut f: F) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, O, E>
+where
+    E: ParseError<Span<&'a str>>,
+    F: Parser<&'a str, O, nom::error::Error<&'a str>>,
+{
+    move |input: Span<&'a str>| {
+        let text = input.as_inner();
+        let at = |rest: &'a str| input.slice(text.offset(rest)..);
+        match f.parse(text) {
+            Ok((rest, output)) => Ok((at(rest), output)),
+            Err(e) => Err(e.map(|e| E::from_error_kind(at(e.input), e.code))),
+        }
+    }
+}
+
+/// Parses an optionally negative decimal integer, returning its value and span.
+pub fn take_i64<'a, E>(input: Span<&'a str>) -> IResult<Span<&'a str>, (i64, Span<&'a str>), E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    let (rest, span) = recognize(pair(opt(char('-')), digit1))(input)?;
+    match span.as_inner().parse() {
+        Ok(value) => Ok((rest, (value, span))),
+        Err(_) => Err(Err::Error(E::from_error_kind(input, ErrorKind::Digit))),
+    }
+}
+
+/// Parses an optionally signed decimal integer, returning the span of the sign if there is one,
+/// the span of the digits, and the value. Fails with `ErrorKind::Digit` if it overflows.
+pub fn signed_int<'a, E>(
+    input: Span<&'a str>,
+) -> IResult<Span<&'a str>, (Option<Span<&'a str>>, Span<&'a str>, i64), E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    let (rest, (sign, digits)) = pair(opt(alt((char('-'), char('+')))), digit1)(input)?;
+    let sign = sign.map(|_| input.slice(..1));
+    match Span::between(input, rest).as_inner().parse() {
+        Ok(value) => Ok((rest, (sign, digits, value))),
+        Err(_) => Err(Err::Error(E::from_error_kind(input, ErrorKind::Digit))),
+    }
+}
+
+/// Consumes a line and its `\n` or `\r\n` terminator, if any, returning the span of the line
+/// without the terminator. Fails at end of input.
+pub fn take_line<'a, E>(input: Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    let text = input.as_inner();
+    if text.is_empty() {
+        return Err(Err::Error(E::from_error_kind(input, ErrorKind::Eof)));
+    }
+    let (len, consumed) = match text.find('\n') {
+        Some(i) if text[..i].ends_with('\r') => (i - 1, i + 1),
+        Some(i) => (i, i + 1),
+        None => (text.len(), text.len()),
+    };
+    Ok((input.slice(consumed..), input.slice(..len)))
+}
+
+/// Consumes the rest of the current line up to, but not including, the char at column `limit`,
+/// with columns as given by [`Span::line_col`].
+pub fn take_until_column<'a, E>(
+    limit: usize,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    move |input: Span<&'a str>| {
+        let (_, mut column) = input.line_col();
+        let mut len = 0;
+        for c in input.as_inner().chars() {
+            if c == '\n' || column >= limit {
+                break;
+            }
+            len += c.len_utf8();
+            column += 1;
+        }
+        Ok(input.take_split(len))
+    }
+}
+
+/// Consumes any whitespace, returning its span.
+pub fn ws0<'a, E>(input: Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    take_while(char::is_whitespace)(input)
+}
+
+/// Consumes at least one whitespace char, returning the span of all the whitespace consumed.
+pub fn ws1<'a, E>(input: Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    take_while1(char::is_whitespace)(input)
+}
+
+/// Like `nom::bytes::complete::take_while_m_n`, consuming between `m` and `n` chars matching
+/// `pred` and returning their span, e.g. for fixed-width fields. Unlike nom's, the bounds count
+/// chars rather than bytes throughout.
+pub fn take_while_m_n_span<'a, E, P>(
+    m: usize,
+    n: usize,
+    pred: P,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>, E>
+where
+    E: ParseError<Span<&'a str>>,
+    P: Fn(char) -> bool,
+{
+    move |input: Span<&'a str>| {
+        let (count, len) = input
+            .as_inner()
+            .chars()
+            .take(n)
+            .take_while(|&c| pred(c))
+            .fold((0, 0), |(count, len), c| (count + 1, len + c.len_utf8()));
+        if count < m {
+            return Err(Err::Error(E::from_error_kind(
+                input,
+                ErrorKind::TakeWhileMN,
+            )));
+        }
+        Ok((input.slice(len..), input.slice(..len)))
+    }
+}
+
+/// Consumes the spaces and tabs starting a line, returning their span and the indentation
+/// width, with tabs advancing to the next multiple of `tab_width` (0 counts tabs as width 1, as
+/// in [`Span::expanded_column`]). Fails with `ErrorKind::Verify` if the input isn't at the start
+/// of a line.
+pub fn indentation<'a, E>(
+    tab_width: usize,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, (Span<&'a str>, usize), E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    move |input: Span<&'a str>| {
+        if !input.at_line_start() {
+            return Err(Err::Error(E::from_error_kind(input, ErrorKind::Verify)));
+        }
+        let (rest, indent) = take_while(|c| c == ' ' || c == '\t')(input)?;
+        Ok((rest, (indent, rest.expanded_column(tab_width) - 1)))
+    }
+}
+
+/// Parses a double-quoted string allowing `\\`, `\"`, `\n` and `\t` escapes. Returns the span
+/// between the quotes, escapes included, and the unescaped contents.
+pub fn string_literal<'a, E>(
+    input: Span<&'a str>,
+) -> IResult<Span<&'a str>, (Span<&'a str>, String), E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    let contents = escaped_transform(
+        is_not("\\\""),
+        '\\',
+        alt((
+            value("\\", char('\\')),
+            value("\"", char('"')),
+            value("\n", char('n')),
+            value("\t", char('t')),
+        )),
+    );
+    let (rest, (span, contents)) = delimited(char('"'), consumed(opt(contents)), char('"'))(input)?;
+    Ok((rest, (span, contents.unwrap_or_default())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::{bytes::complete::tag, error::Error, sequence::preceded};
+
+    #[test]
+    fn test_peek_span() {
+        let s = "let x";
+        let span = Span::from(s);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Span<&str>> {
+            peek_span(tag("let"))(s)
+        }
+
+        assert_eq!(parse(span), Ok((span, Span::new(s, 0, 3))));
+    }
+
+    #[test]
+    fn test_map_spanned() {
+        #[derive(Debug, PartialEq)]
+        struct Num<'a> {
+            value: i64,
+            span: Span<&'a str>,
+        }
+
+        let s = "42 rest";
+        let span = Span::from(s);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Num<'_>> {
+            map_spanned(digit1, |span, digits: Span<&str>| Num {
+                value: digits.value_i64(),
+                span,
+            })(s)
+        }
+
+        assert_eq!(
+            parse(span),
+            Ok((
+                Span::new(s, 2, 7),
+                Num {
+                    value: 42,
+                    span: Span::new(s, 0, 2),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn test_cut_span() {
+        let s = "let 1";
+        let span = Span::from(s);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Span<&str>, SpanError<&str>> {
+            preceded(tag("let"), cut_span(preceded(tag(" "), tag("x"))))(s)
+        }
+
+        assert_eq!(
+            parse(span),
+            Err(Err::Failure(SpanError {
+                span: Span::new(s, 3, 5),
+                kind: ErrorKind::Tag,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_take_i64() {
+        let s = "123 rest";
+        let span = Span::from(s);
+
+        assert_eq!(
+            take_i64::<Error<_>>(span),
+            Ok((Span::new(s, 3, 8), (123, Span::new(s, 0, 3)))),
+        );
+
+        let s = "-7";
+        assert_eq!(
+            take_i64::<Error<_>>(Span::from(s)),
+            Ok((Span::new(s, 2, 2), (-7, Span::new(s, 0, 2)))),
+        );
+
+        let s = "99999999999999999999";
+        assert_eq!(
+            take_i64(Span::from(s)),
+            Err(Err::Error(Error::new(Span::from(s), ErrorKind::Digit))),
+        );
+    }
+
+    #[test]
+    fn test_take_line() {
+        let s = "one\ntwo\r\nthree";
+        let span = Span::from(s);
+
+        let (rest, line) = take_line::<Error<_>>(span).unwrap();
+        assert_eq!((rest, line), (Span::new(s, 4, 14), Span::new(s, 0, 3)));
+
+        let (rest, line) = take_line::<Error<_>>(rest).unwrap();
+        assert_eq!((rest, line), (Span::new(s, 9, 14), Span::new(s, 4, 7)));
+
+        let (rest, line) = take_line::<Error<_>>(rest).unwrap();
+        assert_eq!((rest, line), (Span::new(s, 14, 14), Span::new(s, 9, 14)));
+
+        assert_eq!(
+            take_line(rest),
+            Err(Err::Error(Error::new(rest, ErrorKind::Eof))),
+        );
+    }
+
+    #[test]
+    fn test_take_until_column() {
+        let s = "ab\nCODE  rest\nx";
+        let span = Span::new(s, 3, s.len());
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Span<&str>> {
+            take_until_column(7)(s)
+        }
+
+        let (rest, field) = parse(span).unwrap();
+        assert_eq!((rest, field), (Span::new(s, 9, 15), Span::new(s, 3, 9)));
+        assert_eq!(rest.line_col(), (2, 7));
+
+        assert_eq!(parse(rest), Ok((rest, Span::new(s, 9, 9))));
+        assert_eq!(
+            parse(Span::new(s, 13, 15)),
+            Ok((Span::new(s, 13, 15), Span::new(s, 13, 13))),
+        );
+    }
+
+    #[test]
+    fn test_opt_span() {
+        let s = "pub fn";
+        let span = Span::from(s);
+
+        let mut parse = opt_span::<_, _, Error<_>, _>(tag("pub "));
+
+        assert_eq!(
+            parse(span),
+            Ok((
+                Span::new(s, 4, 6),
+                Some((Span::new(s, 0, 4), Span::new(s, 0, 4))),
+            )),
+        );
+        assert_eq!(parse(Span::new(s, 4, 6)), Ok((Span::new(s, 4, 6), None)));
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let s = r#""hello" rest"#;
+        assert_eq!(
+            string_literal::<Error<_>>(Span::from(s)),
+            Ok((
+                Span::new(s, 7, 12),
+                (Span::new(s, 1, 6), "hello".to_string())
+            )),
+        );
+
+        let s = r#""say \"hi\"\n""#;
+        assert_eq!(
+            string_literal::<Error<_>>(Span::from(s)),
+            Ok((
+                Span::new(s, 14, 14),
+                (Span::new(s, 1, 13), "say \"hi\"\n".to_string())
+            )),
+        );
+
+        let s = r#""""#;
+        assert_eq!(
+            string_literal::<Error<_>>(Span::from(s)),
+            Ok((Span::new(s, 2, 2), (Span::new(s, 1, 1), String::new()))),
+        );
+
+        assert!(string_literal::<Error<_>>(Span::from(r#""open"#)).is_err());
+    }
+
+    #[test]
+    fn test_fold_many0_spanned() {
+        let s = "1+2+3;";
+        let span = Span::from(s);
+
+        let mut parse = fold_many0_spanned::<_, _, Error<_>, _, _, _, _>(
+            preceded(opt(char('+')), digit1),
+            || (0, Vec::new()),
+            |(sum, mut spans), digits: Span<&str>, span| {
+                spans.push(span);
+                (sum + digits.value_i64(), spans)
+            },
+        );
+
+        assert_eq!(
+            parse(span),
+            Ok((
+                Span::new(s, 5, 6),
+                (
+                    Span::new(s, 0, 5),
+                    (
+                        6,
+                        vec![Span::new(s, 0, 1), Span::new(s, 1, 3), Span::new(s, 3, 5)]
+                    ),
+                ),
+            )),
+        );
+        assert_eq!(
+            parse(Span::new(s, 5, 6)),
+            Ok((Span::new(s, 5, 6), (Span::new(s, 5, 5), (0, Vec::new())))),
+        );
+    }
+
+    #[test]
+    fn test_lift() {
+        fn ident(s: &str) -> IResult<&str, &str> {
+            nom::character::complete::alpha1(s)
+        }
+
+        let s = "let x = 1";
+        let span = Span::new(s, 4, 9);
+
+        assert_eq!(
+            lift::<_, Error<_>, _>(ident)(span),
+            Ok((Span::new(s, 5, 9), "x")),
+        );
+        assert_eq!(
+            lift(ident)(Span::new(s, 5, 9)),
+            Err(Err::Error(Error::new(Span::new(s, 5, 9), ErrorKind::Alpha))),
+        );
+    }
+
+    #[test]
+    fn test_all_consuming_span() {
+        let s = "hello world";
+        let mut parse = all_consuming_span(tag("hello"));
+
+        assert_eq!(
+            parse(Span::new(s, 0, 5)),
+            Ok((Span::new(s, 5, 5), Span::new(s, 0, 5)))
+        );
+        assert_eq!(
+            parse(Span::from(s)),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 5, 11),
+                kind: ErrorKind::Eof,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_many_till_span() {
+        let s = "{ a; b; } rest";
+        let statement = nom::sequence::terminated(nom::character::complete::alpha1, tag("; "));
+        let mut block = preceded(
+            tag("{ "),
+            many_till_span::<_, _, _, Error<_>, _, _>(statement, tag("}")),
+        );
+
+        assert_eq!(
+            block(Span::from(s)),
+            Ok((
+                Span::new(s, 9, 14),
+                (
+                    (
+                        Span::new(s, 2, 8),
+                        vec![Span::new(s, 2, 3), Span::new(s, 5, 6)]
+                    ),
+                    (Span::new(s, 8, 9), Span::new(s, 8, 9)),
+                ),
+            )),
+        );
+        assert!(block(Span::from("{ a; b")).is_err());
+    }
+
+    #[test]
+    fn test_balanced() {
+        let mut parens = balanced('(', ')');
+
+        let s = "(a(b)c) rest";
+        assert_eq!(
+            parens(Span::from(s)),
+            Ok((Span::new(s, 7, 12), Span::new(s, 0, 7)))
+        );
+
+        let s = "(a";
+        assert_eq!(
+            parens(Span::from(s)),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 0, 1),
+                kind: ErrorKind::Eof,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+
+        let s = "(a(b(c)";
+        assert_eq!(
+            parens(Span::from(s)),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 2, 3),
+                kind: ErrorKind::Eof,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+
+        let s = "a)";
+        assert_eq!(
+            parens(Span::from(s)),
+            Err(Err::Error(SpanError {
+                span: Span::from(s),
+                kind: ErrorKind::Char,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_atomic() {
+        let s = "x = fn(a, 1)";
+
+        fn call(s: Span<&str>) -> IResult<Span<&str>, Span<&str>, SpanError<&str>> {
+            nom::combinator::recognize(nom::sequence::tuple((
+                tag("fn("),
+                nom::multi::separated_list1(tag(", "), nom::character::complete::alpha1),
+                tag(")"),
+            )))(s)
+        }
+
+        assert_eq!(
+            call(Span::new(s, 4, 12)),
+            Err(Err::Error(SpanError::from_error_kind(
+                Span::new(s, 8, 12),
+                ErrorKind::Tag,
+            ))),
+        );
+        assert_eq!(
+            atomic(call)(Span::new(s, 4, 12)),
+            Err(Err::Error(SpanError::from_error_kind(
+                Span::new(s, 4, 12),
+                ErrorKind::Tag,
+            ))),
+        );
+
+        let s = "fn(a, b)";
+        assert_eq!(
+            atomic(call)(Span::from(s)),
+            Ok((Span::new(s, 8, 8), Span::from(s))),
+        );
+    }
+
+    #[test]
+    fn test_indentation() {
+        let mut parse = indentation::<Error<_>>(4);
+
+        let s = "if x:\n    y\n\t\tz\nw";
+        assert_eq!(
+            parse(Span::new(s, 6, s.len())),
+            Ok((Span::new(s, 10, 17), (Span::new(s, 6, 10), 4))),
+        );
+        assert_eq!(
+            parse(Span::new(s, 12, s.len())),
+            Ok((Span::new(s, 14, 17), (Span::new(s, 12, 14), 8))),
+        );
+        assert_eq!(
+            parse(Span::new(s, 16, s.len())),
+            Ok((Span::new(s, 16, 17), (Span::new(s, 16, 16), 0))),
+        );
+
+        let s = "\tx";
+        assert_eq!(
+            indentation::<Error<_>>(0)(Span::from(s)),
+            Ok((Span::new(s, 1, 2), (Span::new(s, 0, 1), 1))),
+        );
+
+        let s = "ab  cd";
+        assert_eq!(
+            parse(Span::new(s, 2, 6)),
+            Err(Err::Error(Error::new(
+                Span::new(s, 2, 6),
+                ErrorKind::Verify
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_ws() {
+        let s = "x \t\n  y";
+
+        assert_eq!(
+            ws0::<Error<_>>(Span::new(s, 1, 7)),
+            Ok((Span::new(s, 6, 7), Span::new(s, 1, 6))),
+        );
+        assert_eq!(
+            ws0::<Error<_>>(Span::new(s, 6, 7)),
+            Ok((Span::new(s, 6, 7), Span::new(s, 6, 6))),
+        );
+        assert_eq!(
+            ws1::<Error<_>>(Span::new(s, 1, 7)),
+            Ok((Span::new(s, 6, 7), Span::new(s, 1, 6))),
+        );
+        assert_eq!(
+            ws1(Span::new(s, 6, 7)),
+            Err(Err::Error(Error::new(
+                Span::new(s, 6, 7),
+                ErrorKind::TakeWhile1
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_peek_is() {
+        let s = "fn main";
+        let span = Span::from(s);
+
+        assert_eq!(
+            peek_is::<Error<_>>("fn")(span),
+            Ok((span, Span::new(s, 0, 2)))
+        );
+        assert_eq!(
+            peek_is("let")(span),
+            Err(Err::Error(Error::new(span, ErrorKind::Tag))),
+        );
+    }
+
+    #[test]
+    fn test_label() {
+        let s = "let 42";
+        let mut parser = preceded(
+            tag("let "),
+            label(
+                "expected an identifier",
+                nom::character::complete::alpha1::<_, Error<_>>,
+            ),
+        );
+
+        assert_eq!(
+            parser(Span::from(s)),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 4, 6),
+                kind: ErrorKind::Fail,
+                stack: Vec::new(),
+                context: vec![(Span::new(s, 4, 6), "expected an identifier")],
+            })),
+        );
+        assert_eq!(
+            parser(Span::from("let x")),
+            Ok((Span::new("let x", 5, 5), Span::new("let x", 4, 5))),
+        );
+    }
+
+    #[test]
+    fn test_take_while_m_n_span() {
+        let mut digits = take_while_m_n_span::<Error<_>, _>(2, 4, |c: char| c.is_ascii_digit());
+
+        assert_eq!(
+            digits(Span::from("12345")),
+            Ok((Span::new("12345", 4, 5), Span::new("12345", 0, 4))),
+        );
+        assert_eq!(
+            digits(Span::from("12:")),
+            Ok((Span::new("12:", 2, 3), Span::new("12:", 0, 2))),
+        );
+        assert_eq!(
+            digits(Span::from("1:")),
+            Err(Err::Error(Error::new(
+                Span::from("1:"),
+                ErrorKind::TakeWhileMN
+            ))),
+        );
+
+        let mut letters = take_while_m_n_span::<Error<_>, _>(2, 4, char::is_alphabetic);
+        let s = "ééééé!";
+        assert_eq!(
+            letters(Span::new(s, 4, 11)),
+            Ok((Span::new(s, 10, 11), Span::new(s, 4, 10))),
+        );
+        assert_eq!(
+            letters(Span::from("ééé")),
+            Ok((Span::new("ééé", 6, 6), Span::from("ééé"))),
+        );
+        assert!(letters(Span::from("é!")).is_err());
+    }
+
+    #[test]
+    fn test_between_markers() {
+        let mut template = between_markers::<Error<_>>("{{", "}}");
+
+        let s = "a{{x}}b";
+        assert_eq!(
+            template(Span::from(s)),
+            Ok((Span::new(s, 6, 7), (Span::new(s, 3, 4), Span::new(s, 1, 6)))),
+        );
+
+        let s = "{{a}}}}";
+        assert_eq!(
+            template(Span::from(s)),
+            Ok((Span::new(s, 5, 7), (Span::new(s, 2, 3), Span::new(s, 0, 5)))),
+        );
+
+        let s = "a{{x";
+        assert_eq!(
+            template(Span::from(s)),
+            Err(Err::Error(Error::new(
+                Span::new(s, 3, 4),
+                ErrorKind::TakeUntil
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_spanned_iter() {
+        let s = "ab cd  e!";
+        let word = |input| {
+            let (input, word) = nom::character::complete::alpha1::<_, Error<_>>(input)?;
+            let (input, _) = ws0(input)?;
+            Ok((input, word.as_inner()))
+        };
+        let mut iter = SpannedIter::new(Span::from(s), word);
+
+        assert_eq!(iter.next(), Some((Span::new(s, 0, 3), "ab")));
+        assert_eq!(
+            iter.by_ref().collect::<Vec<_>>(),
+            [(Span::new(s, 3, 7), "cd"), (Span::new(s, 7, 8), "e")],
+        );
+        assert_eq!(iter.remaining(), &Span::new(s, 8, 9));
+    }
+
+    #[test]
+    fn test_value_spanned() {
+        let s = "x = true";
+        let mut boolean = preceded(
+            tag("x = "),
+            value_spanned(true, tag::<_, _, Error<_>>("true")),
+        );
+
+        assert_eq!(
+            boolean(Span::from(s)),
+            Ok((Span::new(s, 8, 8), (Span::new(s, 4, 8), true))),
+        );
+    }
+
+    #[test]
+    fn test_with_id() {
+        let s = "ab cd";
+        let counter = Cell::new(0);
+        let mut node = with_id(&counter, nom::character::complete::alpha1::<_, Error<_>>);
+
+        let (rest, first) = node(Span::from(s)).unwrap();
+        let (_, second) = node(rest.slice(1..)).unwrap();
+
+        assert_eq!(first, (0, Span::new(s, 0, 2), Span::new(s, 0, 2)));
+        assert_eq!(second, (1, Span::new(s, 3, 5), Span::new(s, 3, 5)));
+        assert!(node(Span::new(s, 2, 3)).is_err());
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn test_tuple_spanned() {
+        let s = "x = 42";
+        let mut assignment = tuple_spanned((
+            nom::character::complete::alpha1::<_, Error<_>>,
+            tag(" = "),
+            digit1,
+        ));
+
+        assert_eq!(
+            assignment(Span::from(s)),
+            Ok((
+                Span::new(s, 6, 6),
+                (
+                    (Span::new(s, 0, 1), Span::new(s, 0, 1)),
+                    (Span::new(s, 1, 4), Span::new(s, 1, 4)),
+                    (Span::new(s, 4, 6), Span::new(s, 4, 6)),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_separated_pair_spanned() {
+        let s = "foo = bar";
+        let word = nom::character::complete::alpha1::<_, Error<_>>;
+        let mut assignment = separated_pair_spanned(word, tag(" = "), word);
+
+        assert_eq!(
+            assignment(Span::from(s)),
+            Ok((
+                Span::new(s, 9, 9),
+                (
+                    (Span::new(s, 0, 3), Span::new(s, 0, 3)),
+                    (Span::new(s, 6, 9), Span::new(s, 6, 9)),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_signed_int() {
+        let s = "-42";
+        assert_eq!(
+            signed_int::<Error<_>>(Span::from(s)),
+            Ok((
+                Span::new(s, 3, 3),
+                (Some(Span::new(s, 0, 1)), Span::new(s, 1, 3), -42),
+            )),
+        );
+
+        let s = "42,";
+        assert_eq!(
+            signed_int::<Error<_>>(Span::from(s)),
+            Ok((Span::new(s, 2, 3), (None, Span::new(s, 0, 2), 42))),
+        );
+
+        let s = "+7";
+        assert_eq!(
+            signed_int::<Error<_>>(Span::from(s)),
+            Ok((
+                Span::new(s, 2, 2),
+                (Some(Span::new(s, 0, 1)), Span::new(s, 1, 2), 7)
+            )),
+        );
+
+        let s = "-9223372036854775809";
+        assert_eq!(
+            signed_int(Span::from(s)),
+            Err(Err::Error(Error::new(Span::from(s), ErrorKind::Digit))),
+        );
+    }
+
+    #[test]
+    fn test_terminated_span() {
+        let s = "foo;";
+        let mut statement =
+            terminated_span(nom::character::complete::alpha1::<_, Error<_>>, tag(";"));
+
+        assert_eq!(
+            statement(Span::from(s)),
+            Ok((Span::new(s, 4, 4), (Span::new(s, 0, 3), Span::new(s, 0, 3)))),
+        );
+        assert!(statement(Span::from("foo")).is_err());
+    }
+
+    #[test]
+    fn test_preceded_span() {
+        let s = "-42";
+        let mut negated = preceded_span(tag::<_, _, Error<_>>("-"), take_i64);
+
+        assert_eq!(
+            negated(Span::from(s)),
+            Ok((
+                Span::new(s, 3, 3),
+                (Span::new(s, 1, 3), (42, Span::new(s, 1, 3)))
+            )),
+        );
+    }
+
+    #[test]
+    fn test_separated_list_trailing_spanned() {
+        let mut list = separated_list_trailing_spanned(
+            tag(","),
+            nom::character::complete::alpha1::<_, Error<_>>,
+        );
+
+        let s = "a,bc)";
+        let (rest, (span, items, trailing)) = list(Span::from(s)).unwrap();
+        assert_eq!(
+            (rest, span, trailing),
+            (Span::new(s, 4, 5), Span::new(s, 0, 4), false)
+        );
+        assert_eq!(
+            items,
+            [
+                (Span::new(s, 0, 1), Span::new(s, 0, 1)),
+                (Span::new(s, 2, 4), Span::new(s, 2, 4)),
+            ],
+        );
+
+        let s = "a,bc,)";
+        let (rest, (span, items, trailing)) = list(Span::from(s)).unwrap();
+        assert_eq!(
+            (rest, span, trailing),
+            (Span::new(s, 5, 6), Span::new(s, 0, 5), true)
+        );
+        assert_eq!(items.len(), 2);
+
+        assert!(list(Span::from(",a")).is_err());
+    }
+}
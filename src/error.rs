@@ -0,0 +1,147 @@
+//! Error types that remember where in the source a parse failed.
+
+use crate::Span;
+use nom::error::{ContextError, ErrorKind, ParseError};
+use std::ops::Range;
+
+/// A parse error carrying the span at which parsing failed, plus the spans and kinds of the
+/// enclosing parsers it propagated through and the spans and messages of any
+/// `nom::error::context` it passed, both innermost first.
+#[derive(Clone, PartialEq)]
+pub struct SpanError<T> {
+    pub span: Span<T>,
+    pub kind: ErrorKind,
+    pub stack: Vec<(Span<T>, ErrorKind)>,
+    pub context: Vec<(Span<T>, &'static str)>,
+}
+
+impl<T> std::fmt::Debug for SpanError<T>
+where
+    T: std::fmt::Debug + nom::Slice<Range<usize>>,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SpanError")
+            .field("span", &self.span)
+            .field("kind", &self.kind)
+            .field("stack", &self.stack)
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+impl<T> ParseError<Span<T>> for SpanError<T> {
+    fn from_error_kind(input: Span<T>, kind: ErrorKind) -> Self {
+        Self {
+            span: input,
+            kind,
+            stack: Vec::new(),
+            context: Vec::new(),
+        }
+    }
+
+    fn append(input: Span<T>, kind: ErrorKind, mut other: Self) -> Self {
+        other.stack.push((input, kind));
+        other
+    }
+}
+
+impl<T> ContextError<Span<T>> for SpanError<T> {
+    fn add_context(input: Span<T>, ctx: &'static str, mut other: Self) -> Self {
+        other.context.push((input, ctx));
+        other
+    }
+}
+
+/// Returned by [`Span::try_new`] when the start offset is after the end offset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for InvalidRange {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "span start {} is after end {}", self.start, self.end)
+    }
+}
+
+impl std::error::Error for InvalidRange {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::{
+        bytes::complete::tag, character::complete::alpha1, error::context, multi::many1,
+        sequence::preceded, Err, IResult, Slice,
+    };
+
+    #[test]
+    fn test_tag_error() {
+        let s = "hello world";
+        let span = Span::from(s);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Span<&str>, SpanError<&str>> {
+            tag("world")(s)
+        }
+
+        assert_eq!(
+            parse(span.slice(6..)),
+            Ok((Span::new(s, 11, 11), Span::new(s, 6, 11))),
+        );
+        assert_eq!(
+            parse(span),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 0, 11),
+                kind: ErrorKind::Tag,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Vec<Span<&str>>, SpanError<&str>> {
+            many1(tag("hello"))(s)
+        }
+
+        assert_eq!(
+            parse(span),
+            Err(Err::Error(SpanError {
+                span,
+                kind: ErrorKind::Tag,
+                stack: vec![(span, ErrorKind::Many1)],
+                context: Vec::new(),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_context() {
+        let s = "let 1";
+        let span = Span::from(s);
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, Span<&str>, SpanError<&str>> {
+            context(
+                "let binding",
+                preceded(tag("let "), context("identifier", alpha1)),
+            )(s)
+        }
+
+        assert_eq!(
+            parse(span),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 4, 5),
+                kind: ErrorKind::Alpha,
+                stack: Vec::new(),
+                context: vec![
+                    (Span::new(s, 4, 5), "identifier"),
+                    (Span::new(s, 0, 5), "let binding"),
+                ],
+            })),
+        );
+    }
+}
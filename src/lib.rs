@@ -1,11 +1,43 @@
 use nom::{
     error::{ErrorKind, ParseError},
-    Compare, Err, IResult, InputIter, InputLength, InputTake, InputTakeAtPosition, Offset, Slice,
+    Compare, Err, ExtendInto, IResult, InputIter, InputLength, InputTake, InputTakeAtPosition,
+    Offset, Slice,
 };
 use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
 use unwrap::unwrap;
 
+pub mod combinator;
+pub mod error;
+mod macros;
+pub mod records;
+pub mod source;
+pub mod span_set;
+pub mod spanned;
+
+use error::InvalidRange;
+
+/// Number of `\n` bytes in `s`, via `memchr` when that feature is enabled.
+fn count_newlines(s: &str) -> usize {
+    #[cfg(feature = "memchr")]
+    {
+        memchr::memchr_iter(b'\n', s.as_bytes()).count()
+    }
+    #[cfg(not(feature = "memchr"))]
+    {
+        count_newlines_naive(s)
+    }
+}
+
+#[cfg(any(test, not(feature = "memchr")))]
+fn count_newlines_naive(s: &str) -> usize {
+    s.bytes().filter(|&b| b == b'\n').count()
+}
+
 /// Represents a subslice of T specified by a range. Use it with nom as you would a string.
+///
+/// Streaming parsers work too, returning `Incomplete` when the span runs out. To resume, note
+/// where the remaining input starts, append to the buffer and continue from
+/// `Span::new(buffer, start, buffer.len())`, which keeps offsets absolute.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Span<T> {
     inner: T,
@@ -14,6 +46,442 @@ pub struct Span<T> {
 }
 
 impl<'a> Span<&'a str> {
+    /// Builds a span over `s` from byte offsets, e.g. ones taken from a byte view of the same
+    /// source, or `None` if either offset falls inside a char or past the end.
+    pub fn from_str_bytes(s: &'a str, start: usize, end: usize) -> Option<Self> {
+        (start <= end && s.is_char_boundary(start) && s.is_char_boundary(end))
+            .then(|| Self::new(s, start, end))
+    }
+
+    /// The span of `sub` within `source`, found from their addresses, or `None` if `sub` isn't
+    /// a subslice of `source`.
+    pub fn from_substr(source: &'a str, sub: &'a str) -> Option<Self> {
+        let start = (sub.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+        let end = start.checked_add(sub.len())?;
+        (end <= source.len()).then(|| Self::new(source, start, end))
+    }
+
+    /// A span over all of `cow`. There's deliberately no `Span<Cow<str>>`: slicing it would have
+    /// to allocate whenever the `Cow` is owned, so borrow the normalized text for the duration
+    /// of the parse instead.
+    #[allow(clippy::ptr_arg)]
+    pub fn from_cow(cow: &'a std::borrow::Cow<'_, str>) -> Self {
+        Span::from(cow.as_ref())
+    }
+
+    /// Spans of the non-overlapping occurrences of `pat` within the span, like
+    /// `str::match_indices`.
+    pub fn match_indices<'p>(&self, pat: &'p str) -> impl Iterator<Item = Span<&'a str>> + 'p
+    where
+        'a: 'p,
+    {
+        let (inner, start) = (self.inner, self.start);
+        self.as_inner()
+            .match_indices(pat)
+            .map(move |(i, m)| Span::new(inner, start + i, start + i + m.len()))
+    }
+
+    /// Splits the span on every char matching `pred`, yielding the runs between them. Empty runs,
+    /// e.g. between two adjacent separators, are only yielded if `keep_empty` is set.
+    pub fn split_on<P: Fn(char) -> bool + 'a>(
+        &self,
+        pred: P,
+        keep_empty: bool,
+    ) -> impl Iterator<Item = Span<&'a str>> {
+        let span = *self;
+        span.as_inner()
+            .split(pred)
+            .map(move |run| {
+                let start = span.start + span.as_inner().offset(run);
+                Span::new(span.inner, start, start + run.len())
+            })
+            .filter(move |run| keep_empty || run.start < run.end)
+    }
+
+    /// The span of the first match of `re` within the span.
+    #[cfg(feature = "regex")]
+    pub fn regex_match(&self, re: &regex::Regex) -> Option<Self> {
+        re.find(self.as_inner())
+            .map(|m| Span::new(self.inner, self.start + m.start(), self.start + m.end()))
+    }
+
+    /// Spans of the successive non-overlapping matches of `re` within the span.
+    #[cfg(feature = "regex")]
+    pub fn regex_matches<'r>(&self, re: &'r regex::Regex) -> impl Iterator<Item = Self> + 'r
+    where
+        'a: 'r,
+    {
+        let (inner, start) = (self.inner, self.start);
+        re.find_iter(self.as_inner())
+            .map(move |m| Span::new(inner, start + m.start(), start + m.end()))
+    }
+
+    /// Copies the spanned text into a standalone span covering all of it. The copy no longer
+    /// knows where it was in the original source, so its offsets start again at 0.
+    pub fn into_owned(self) -> Span<String> {
+        let text = self.as_inner().to_string();
+        let len = text.len();
+        Span::new(text, 0, len)
+    }
+
+    /// Skips a leading UTF-8 byte order mark, if there is one.
+    pub fn strip_bom(&self) -> Self {
+        match self.as_inner().strip_prefix('\u{FEFF}') {
+            Some(_) => Span::new(self.inner, self.start + '\u{FEFF}'.len_utf8(), self.end),
+            None => *self,
+        }
+    }
+
+    /// The span's chars, each with its one-char span, like `str::char_indices`.
+    pub fn char_indices_spanned(&self) -> impl Iterator<Item = (char, Span<&'a str>)> {
+        let (inner, start) = (self.inner, self.start);
+        self.as_inner()
+            .char_indices()
+            .map(move |(i, c)| (c, Span::new(inner, start + i, start + i + c.len_utf8())))
+    }
+
+    /// One-char spans of the span's chars, from last to first.
+    pub fn char_spans_rev(&self) -> impl Iterator<Item = Span<&'a str>> {
+        let (inner, start) = (self.inner, self.start);
+        self.as_inner()
+            .char_indices()
+            .rev()
+            .map(move |(i, c)| Span::new(inner, start + i, start + i + c.len_utf8()))
+    }
+
+    /// Whether this is [`Span::dummy`]. Spans over an empty source, such as an EOF error in an
+    /// empty file, are real spans and aren't dummies.
+    pub fn is_dummy(&self) -> bool {
+        std::ptr::eq(self.inner.as_ptr(), DUMMY_SOURCE.as_ptr()) && self.start == 0 && self.end == 0
+    }
+
+    /// Whether the spanned text equals `other`, ignoring ASCII case.
+    pub fn eq_ignore_ascii_case(&self, other: &str) -> bool {
+        self.as_inner().eq_ignore_ascii_case(other)
+    }
+
+    /// Whether `other_source` has the same text as this span at the same range, e.g. to decide
+    /// whether a cached subtree survives an edit. False if the range isn't valid in it.
+    pub fn content_matches(&self, other_source: &str) -> bool {
+        other_source.get(self.range()) == Some(self.as_inner())
+    }
+
+    /// Length in bytes of the longest prefix the texts of `a` and `b` share, counting whole chars
+    /// only.
+    pub fn common_prefix(a: &Self, b: &Self) -> usize {
+        a.as_inner()
+            .chars()
+            .zip(b.as_inner().chars())
+            .take_while(|(x, y)| x == y)
+            .map(|(c, _)| c.len_utf8())
+            .sum()
+    }
+
+    /// The span truncated to the prefix its text shares with `other`'s.
+    pub fn common_prefix_span(&self, other: &Self) -> Self {
+        self.truncate(Self::common_prefix(self, other))
+    }
+
+    /// The whitespace in the source immediately before the span, which is empty if there's
+    /// none.
+    pub fn leading_trivia(&self) -> Self {
+        let before = &self.inner[..self.start];
+        let start = before.trim_end().len();
+        Span::new(self.inner, start, self.start)
+    }
+
+    /// Splits the span into its leading spaces and tabs and the content after them, typically
+    /// for a span covering one line.
+    pub fn split_indent(&self) -> (Self, Self) {
+        let text = self.as_inner();
+        let indent = text.len() - text.trim_start_matches([' ', '\t']).len();
+        (
+            Span::new(self.inner, self.start, self.start + indent),
+            Span::new(self.inner, self.start + indent, self.end),
+        )
+    }
+
+    /// The span grown to cover the whole word, of alphanumerics and underscores, at each of its
+    /// ends, as when double-clicking. An end that isn't on a word char stays put, whereas an
+    /// empty span, like a caret, grows into the words on either side.
+    pub fn expand_to_word(&self) -> Self {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let width = |chars: &mut dyn Iterator<Item = char>| -> usize {
+            chars.take_while(is_word).map(char::len_utf8).sum()
+        };
+        let text = self.as_inner();
+        let mut start = self.start;
+        if text.chars().next().is_none_or(|c| is_word(&c)) {
+            start -= width(&mut self.inner[..self.start].chars().rev());
+        }
+        let mut end = self.end;
+        if text.chars().next_back().is_none_or(|c| is_word(&c)) {
+            end += width(&mut self.inner[self.end..].chars());
+        }
+        Span::new(self.inner, start, end)
+    }
+
+    /// The nearest char boundary at or before the absolute `offset`, clamped to the span, so
+    /// that slicing there won't panic.
+    pub fn snap_to_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.clamp(self.start, self.end);
+        while !self.inner.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// Views the same offsets over the source bytes.
+    pub fn as_bytes_span(&self) -> Span<&'a [u8]> {
+        Span::new(self.inner.as_bytes(), self.start, self.end)
+    }
+
+    /// Start of the line containing `offset`.
+    fn line_start(&self, offset: usize) -> usize {
+        self.inner[..offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// End of the line containing `offset`, excluding the newline.
+    fn line_end(&self, offset: usize) -> usize {
+        self.inner[offset..]
+            .find('\n')
+            .map_or(self.inner.len(), |i| offset + i)
+    }
+
+    /// Whether the span starts at the start of a line, i.e. at 0 or right after a newline.
+    pub fn at_line_start(&self) -> bool {
+        self.line_start(self.start) == self.start
+    }
+
+    /// Whether the span ends at the end of a line, i.e. at the end of the source or right before
+    /// a newline.
+    pub fn at_line_end(&self) -> bool {
+        self.line_end(self.end) == self.end
+    }
+
+    /// The span grown to cover the full lines it touches, excluding the final newline. As in
+    /// [`Span::split_by_lines`], a trailing newline doesn't count as touching the following line.
+    pub fn to_full_lines(&self) -> Self {
+        let end = if self.start < self.end && self.inner[..self.end].ends_with('\n') {
+            self.end - 1
+        } else {
+            self.line_end(self.end)
+        };
+        Span::new(self.inner, self.line_start(self.start), end)
+    }
+
+    /// The full lines the span touches, plus up to `context_lines` lines either side.
+    pub fn snippet(&self, context_lines: usize) -> Span<&'a str> {
+        let Span {
+            mut start, mut end, ..
+        } = self.to_full_lines();
+        for _ in 0..context_lines {
+            if start > 0 {
+                start = self.line_start(start - 1);
+            }
+            if end < self.inner.len() {
+                end = self.line_end(end + 1);
+            }
+        }
+        Span::new(self.inner, start, end)
+    }
+
+    /// The full lines the span touches, each with its text before, within, and after the span.
+    #[cfg(any(feature = "color", feature = "diagnostics"))]
+    fn marked_lines(&self) -> impl Iterator<Item = (Self, [&'a str; 3])> {
+        let span = *self;
+        span.to_full_lines()
+            .split_by_lines()
+            .into_iter()
+            .map(move |line| {
+                let start = span.start.clamp(line.start, line.end);
+                let end = span.end.clamp(line.start, line.end);
+                let parts = [
+                    &span.inner[line.start..start],
+                    &span.inner[start..end],
+                    &span.inner[end..line.end],
+                ];
+                (line, parts)
+            })
+    }
+
+    /// Carets under `marked`, at least one, indented past `before`.
+    #[cfg(any(feature = "color", feature = "diagnostics"))]
+    fn carets(before: &str, marked: &str) -> String {
+        let indent = " ".repeat(before.chars().count());
+        indent + &"^".repeat(marked.chars().count().max(1))
+    }
+
+    /// The lines the span touches, each with the span's part in ANSI reverse video and followed
+    /// by a line of carets under it.
+    #[cfg(feature = "color")]
+    pub fn highlight_ansi(&self) -> String {
+        let mut out = String::new();
+        for (_, [before, marked, after]) in self.marked_lines() {
+            out.push_str(&format!("{before}\x1b[7m{marked}\x1b[0m{after}\n"));
+            out.push_str(&Self::carets(before, marked));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A rustc-style diagnostic: `message`, a `filename:line:col` header, then the lines the
+    /// span touches with line numbers in a gutter and carets under the span.
+    #[cfg(feature = "diagnostics")]
+    pub fn render_diagnostic(&self, filename: &str, message: &str) -> String {
+        let lines: Vec<_> = self
+            .marked_lines()
+            .map(|(line, parts)| (line.line_number().to_string(), parts))
+            .collect();
+        let width = lines.last().map_or(0, |(number, _)| number.len());
+        let gutter = " ".repeat(width);
+        let mut out = format!(
+            "{message}\n{gutter}--> {}\n",
+            self.display_location(filename)
+        );
+        out.push_str(&format!("{gutter} |\n"));
+        for (number, [before, marked, after]) in lines {
+            out.push_str(&format!("{number:>width$} | {before}{marked}{after}\n"));
+            out.push_str(&format!("{gutter} | {}\n", Self::carets(before, marked)));
+        }
+        out
+    }
+
+    /// Splits the span into one span per line it touches, clipped to the span and excluding the
+    /// newlines. A trailing newline doesn't count as touching the following line.
+    pub fn split_by_lines(&self) -> Vec<Span<&'a str>> {
+        let mut lines = Vec::new();
+        let mut start = self.start;
+        loop {
+            let end = self.line_end(start).min(self.end);
+            lines.push(Span::new(self.inner, start, end));
+            if end + 1 >= self.end {
+                return lines;
+            }
+            start = end + 1;
+        }
+    }
+
+    /// One-based line of the start of the span, cheaper than [`Span::line_col`] when the column
+    /// isn't needed.
+    pub fn line_number(&self) -> usize {
+        count_newlines(&self.inner[..self.start]) + 1
+    }
+
+    /// One-based line and column of the start of the span, with the column counted in chars.
+    pub fn line_col(&self) -> (usize, usize) {
+        let line_start = self.line_start(self.start);
+        (
+            self.line_number(),
+            self.inner[line_start..self.start].chars().count() + 1,
+        )
+    }
+
+    /// One-based line and column of the last char of the span, as in diagnostic formats with
+    /// inclusive ends. A zero-width span has no last char, so this is its start.
+    pub fn end_line_col_inclusive(&self) -> (usize, usize) {
+        let last = self
+            .as_inner()
+            .char_indices()
+            .next_back()
+            .map_or(self.start, |(i, _)| self.start + i);
+        Span::new(self.inner, last, last).line_col()
+    }
+
+    /// One-based visual column of the start of the span, like [`Span::line_col`] but with tabs
+    /// advancing to the next multiple of `tab_width`. A `tab_width` of 0 is taken as 1, so tabs
+    /// count as one column like any other char.
+    pub fn expanded_column(&self, tab_width: usize) -> usize {
+        let tab_width = tab_width.max(1);
+        let line_start = self.line_start(self.start);
+        let width = self.inner[line_start..self.start]
+            .chars()
+            .fold(0, |width, c| match c {
+                '\t' => (width / tab_width + 1) * tab_width,
+                _ => width + 1,
+            });
+        width + 1
+    }
+
+    /// Whether the spanned text contains no `\n`.
+    pub fn is_single_line(&self) -> bool {
+        !self.as_inner().contains('\n')
+    }
+
+    /// Number of lines the span touches. An empty span still touches the line it's on.
+    pub fn line_count(&self) -> usize {
+        count_newlines(self.as_inner()) + 1
+    }
+
+    /// `filename:line:col` for the start of the span, as editors and terminals recognize.
+    pub fn display_location(&self, filename: &str) -> String {
+        let (line, col) = self.line_col();
+        format!("{}:{}:{}", filename, line, col)
+    }
+
+    /// Zero-based line and column of the start of the span, with the column counted in UTF-16
+    /// code units as in a Language Server Protocol `Position`.
+    pub fn utf16_line_col(&self) -> (usize, usize) {
+        let before = &self.inner[..self.start];
+        let line_start = self.line_start(self.start);
+        (
+            count_newlines(before),
+            before[line_start..].encode_utf16().count(),
+        )
+    }
+
+    /// Number of chars in the span, as opposed to its length in bytes.
+    pub fn char_count(&self) -> usize {
+        self.as_inner().chars().count()
+    }
+
+    /// Number of occurrences of `c` in the span.
+    pub fn count_char(&self, c: char) -> usize {
+        self.as_inner().matches(c).count()
+    }
+
+    /// Number of extended grapheme clusters in the span, i.e. user-perceived characters.
+    #[cfg(feature = "unicode")]
+    pub fn grapheme_count(&self) -> usize {
+        use unicode_segmentation::UnicodeSegmentation;
+        self.as_inner().graphemes(true).count()
+    }
+
+    /// Absolute byte offset of the `char_idx`th char of the span, if there is one.
+    pub fn byte_offset_of_char(&self, char_idx: usize) -> Option<usize> {
+        self.as_inner()
+            .char_indices()
+            .nth(char_idx)
+            .map(|(i, _)| self.start + i)
+    }
+
+    /// The span covering `tokens` and their texts concatenated, for reassembling a token that
+    /// was split up, or `None` if there are no tokens. The tokens must share a source and be in
+    /// order.
+    pub fn merge_tokens(tokens: &[Self]) -> Option<(Self, String)> {
+        let (first, last) = (tokens.first()?, tokens.last()?);
+        let text = tokens.iter().map(Span::as_inner).collect();
+        Some((Span::to(*first, *last), text))
+    }
+
+    /// Replaces the span's range of `source`, usually the span's own source, with `replacement`.
+    /// Returns the edited text and the range the replacement covers in it; the span itself can't
+    /// be returned as it would borrow the new text.
+    pub fn replace(&self, source: &str, replacement: &str) -> (String, Range<usize>) {
+        let edited = [&source[..self.start], replacement, &source[self.end..]].concat();
+        (edited, self.start..self.start + replacement.len())
+    }
+
+    /// The lowercased text alongside the span it came from.
+    pub fn to_lowercase_spanned(&self) -> (String, Self) {
+        (self.as_inner().to_lowercase(), *self)
+    }
+
+    /// The uppercased text alongside the span it came from.
+    pub fn to_uppercase_spanned(&self) -> (String, Self) {
+        (self.as_inner().to_uppercase(), *self)
+    }
+
     pub fn value_i64(&self) -> i64 {
         unwrap!(
             self.as_inner().parse::<i64>(),
@@ -23,6 +491,79 @@ impl<'a> Span<&'a str> {
     }
 }
 
+/// Backing for [`Span::dummy`]. A static has an address of its own, unlike an empty string
+/// literal, so dummies can be told apart from other empty spans by pointer.
+static DUMMY_SOURCE: [u8; 1] = [0];
+
+impl Span<&'static str> {
+    /// A placeholder span for nodes with no place in the source, such as desugared ones. See
+    /// [`Span::is_dummy`].
+    pub fn dummy() -> Self {
+        let source = std::str::from_utf8(&DUMMY_SOURCE[..0]).unwrap();
+        Span::new(source, 0, 0)
+    }
+}
+
+impl Span<String> {
+    pub fn as_str(&self) -> &str {
+        &self.inner[self.start..self.end]
+    }
+}
+
+/// Byte spans work with `nom::bits` as they are: bit parsers see a `(Span<&[u8]>, usize)` of the
+/// current byte and bit, so the byte-level position is kept throughout, and `bits` hands back a
+/// span starting after the last byte it touched.
+impl<'a> Span<&'a [u8]> {
+    /// Views the same offsets over the source as a `str`, validating that the source is UTF-8
+    /// and that both offsets fall on char boundaries.
+    pub fn to_str_span(&self) -> Option<Span<&'a str>> {
+        let inner = std::str::from_utf8(self.inner).ok()?;
+        (inner.is_char_boundary(self.start) && inner.is_char_boundary(self.end))
+            .then(|| Span::new(inner, self.start, self.end))
+    }
+
+    /// Splits the span into sub-spans of `size` bytes, the last of which may be shorter. Panics if
+    /// `size` is 0.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = Span<&'a [u8]>> {
+        let (inner, end) = (self.inner, self.end);
+        (self.start..end)
+            .step_by(size)
+            .map(move |start| Span::new(inner, start, (start + size).min(end)))
+    }
+
+    /// Overlapping sub-spans of `size` bytes, one starting at each byte like `slice::windows`.
+    /// Yields nothing if the span is shorter than `size`. Panics if `size` is 0.
+    pub fn windows(&self, size: usize) -> impl Iterator<Item = Span<&'a [u8]>> {
+        assert!(size != 0, "window size must be non-zero");
+        let inner = self.inner;
+        (self.start..(self.end + 1).saturating_sub(size))
+            .map(move |start| Span::new(inner, start, start + size))
+    }
+}
+
+/// Paths are only spannable on unix, where an `OsStr` is an arbitrary byte string. Elsewhere
+/// `OsStr` has no stable byte representation to slice.
+#[cfg(unix)]
+impl<'a> Span<&'a [u8]> {
+    pub fn from_os_str(s: &'a std::ffi::OsStr) -> Self {
+        use std::os::unix::ffi::OsStrExt;
+        Span::from(s.as_bytes())
+    }
+
+    pub fn from_path(path: &'a std::path::Path) -> Self {
+        Self::from_os_str(path.as_os_str())
+    }
+
+    pub fn as_os_str(&self) -> &'a std::ffi::OsStr {
+        use std::os::unix::ffi::OsStrExt;
+        std::ffi::OsStr::from_bytes(self.as_inner())
+    }
+
+    pub fn as_path(&self) -> &'a std::path::Path {
+        std::path::Path::new(self.as_os_str())
+    }
+}
+
 impl<T> std::fmt::Debug for Span<T>
 where
     T: std::fmt::Debug + Slice<Range<usize>>,
@@ -35,11 +576,52 @@ where
     }
 }
 
+/// Writes the spanned text. The alternate form `{:#}` also writes where the text is, which is
+/// how `nom_supreme`'s `ErrorTree` displays error locations:
+///
+/// ```
+/// use nom::IResult;
+/// use nom_supreme::{error::ErrorTree, tag::complete::tag};
+/// use span::Span;
+///
+/// fn hello(s: Span<&str>) -> IResult<Span<&str>, Span<&str>, ErrorTree<Span<&str>>> {
+///     tag("hello")(s)
+/// }
+///
+/// let err = match hello(Span::new("say goodbye", 4, 11)) {
+///     Err(nom::Err::Error(err)) => err,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(err.to_string(), r#"expected "hello" at goodbye at 4..11"#);
+/// ```
+impl<T> std::fmt::Display for Span<T>
+where
+    T: std::fmt::Display + Slice<Range<usize>>,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.as_inner())?;
+        if fmt.alternate() {
+            write!(fmt, " at {:?}", self.range())?;
+        }
+        Ok(())
+    }
+}
+
 impl<T> Span<T> {
+    /// Panics in debug builds if `start > end`.
     pub fn new(inner: T, start: usize, end: usize) -> Self {
+        debug_assert!(start <= end, "span start {} is after end {}", start, end);
         Self { inner, start, end }
     }
 
+    pub fn try_new(inner: T, start: usize, end: usize) -> Result<Self, InvalidRange> {
+        if start <= end {
+            Ok(Self { inner, start, end })
+        } else {
+            Err(InvalidRange { start, end })
+        }
+    }
+
     #[allow(dead_code)]
     pub fn end(inner: T) -> Self
     where
@@ -49,6 +631,15 @@ impl<T> Span<T> {
         Span::new(inner, length, length)
     }
 
+    /// A span covering all of `inner`. This is the same as `Span::from(inner)`, spelled out for
+    /// symmetry with [`Span::end`].
+    pub fn full(inner: T) -> Self
+    where
+        T: InputLength,
+    {
+        Self::from(inner)
+    }
+
     pub fn as_inner(&self) -> T
     where
         T: Slice<Range<usize>>,
@@ -56,10 +647,25 @@ impl<T> Span<T> {
         self.inner.slice(self.start..self.end)
     }
 
+    /// The spanned text of a [`source::SpanSource`], which unlike [`Span::as_inner`] works for
+    /// sources like ropes that can't be sliced into themselves.
+    pub fn text(&self) -> std::borrow::Cow<'_, str>
+    where
+        T: source::SpanSource,
+    {
+        self.inner.text(self.start..self.end)
+    }
+
     pub fn between(first: Span<T>, second: Span<T>) -> Self
     where
         T: Clone,
     {
+        debug_assert!(
+            first.start <= second.start,
+            "span {:?} starts before {:?}",
+            second.range(),
+            first.range(),
+        );
         Span::new(first.inner.clone(), first.start, second.start)
     }
 
@@ -67,12 +673,181 @@ impl<T> Span<T> {
     where
         T: Clone,
     {
+        debug_assert!(
+            first.start <= second.end,
+            "span {:?} ends before {:?} starts",
+            second.range(),
+            first.range(),
+        );
         Self::new(first.inner.clone(), first.start, second.end)
     }
 
+    /// Splits the ground covered by `a` and `b` into the parts only in `a`, the part in both,
+    /// and the parts only in `b`. A span containing the other has parts on both sides of the
+    /// overlap, so each exclusive side is up to two spans in order; empty parts are left out.
+    /// Both spans must share a base.
+    pub fn partition(a: Span<T>, b: Span<T>) -> (Vec<Self>, Option<Self>, Vec<Self>)
+    where
+        T: Clone,
+    {
+        let part = |start: usize, end: usize| {
+            (start < end).then(|| Self::new(a.inner.clone(), start, end))
+        };
+        let (shared_start, shared_end) = (a.start.max(b.start), a.end.min(b.end));
+        let shared = part(shared_start, shared_end);
+        let only = |span: &Span<T>| -> Vec<Self> {
+            match shared {
+                Some(_) => [part(span.start, shared_start), part(shared_end, span.end)]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                None => part(span.start, span.end).into_iter().collect(),
+            }
+        };
+        (only(&a), shared.clone(), only(&b))
+    }
+
+    /// Key for memoizing parse results at this position, as in a packrat parser. Within one
+    /// parse the base never changes, so the start offset alone identifies the input; pair it
+    /// with a rule id rather than hashing the whole span.
+    pub fn memo_key(&self) -> usize {
+        self.start
+    }
+
+    #[cfg(feature = "text-size")]
+    pub fn from_text_range(inner: T, range: text_size::TextRange) -> Self {
+        Self::new(inner, range.start().into(), range.end().into())
+    }
+
+    /// Start of the span as a `codespan` index. Panics if it doesn't fit in a `u32`.
+    #[cfg(feature = "codespan")]
+    pub fn byte_index(&self) -> codespan::ByteIndex {
+        codespan::ByteIndex(codespan_offset(self.start))
+    }
+
+    /// The span as a `codespan` span. Panics if an offset doesn't fit in a `u32`.
+    #[cfg(feature = "codespan")]
+    pub fn byte_span(&self) -> codespan::Span {
+        codespan::Span::new(codespan_offset(self.start), codespan_offset(self.end))
+    }
+
+    /// Like `Slice::slice`, but returns `None` instead of overflowing when the offsets don't fit
+    /// in a `usize`.
+    pub fn checked_slice(&self, range: Range<usize>) -> Option<Self>
+    where
+        T: Clone,
+    {
+        let start = self.start.checked_add(range.start)?;
+        let end = self.start.checked_add(range.end)?;
+        Some(Self::new(self.inner.clone(), start, end))
+    }
+
     pub fn range(&self) -> Range<usize> {
         self.start..self.end
     }
+
+    /// Whether the offsets are in order and within the source, for asserting on after chains of
+    /// slicing that might have gone wrong.
+    pub fn is_valid(&self) -> bool
+    where
+        T: InputLength,
+    {
+        self.start <= self.end && self.end <= self.inner.input_len()
+    }
+
+    /// Start offset, named as in pest for ease of migration.
+    pub fn start_pos(&self) -> usize {
+        self.start
+    }
+
+    /// End offset, named as in pest for ease of migration.
+    pub fn end_pos(&self) -> usize {
+        self.end
+    }
+
+    /// Same as [`Span::new`], named as in pest for ease of migration.
+    pub fn from_positions(inner: T, start: usize, end: usize) -> Self {
+        Self::new(inner, start, end)
+    }
+
+    /// Moves both ends of the span by `delta`, saturating at 0. Use this to keep a span in sync
+    /// after text is inserted or removed before it.
+    pub fn shifted(&self, delta: isize) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(
+            self.inner.clone(),
+            self.start.saturating_add_signed(delta),
+            self.end.saturating_add_signed(delta),
+        )
+    }
+
+    /// Splits the span at the absolute `offset` into the parts before and after it, or `None` if
+    /// `offset` is outside the span. Unlike `InputTake::take_split`, the first part comes first.
+    pub fn split_at_absolute(&self, offset: usize) -> Option<(Self, Self)>
+    where
+        T: Clone,
+    {
+        (self.start..=self.end).contains(&offset).then(|| {
+            (
+                Self::new(self.inner.clone(), self.start, offset),
+                Self::new(self.inner.clone(), offset, self.end),
+            )
+        })
+    }
+
+    /// The first `max_len` bytes of the span, or the whole span if it's shorter.
+    pub fn truncate(&self, max_len: usize) -> Self
+    where
+        T: Clone,
+    {
+        let end = self.end.min(self.start.saturating_add(max_len));
+        Self::new(self.inner.clone(), self.start, end)
+    }
+
+    /// The rest of the span after its first `n` bytes, or an empty span at its end if it's
+    /// shorter.
+    pub fn skip(&self, n: usize) -> Self
+    where
+        T: Clone,
+    {
+        let start = self.end.min(self.start.saturating_add(n));
+        Self::new(self.inner.clone(), start, self.end)
+    }
+
+    /// The first `count` bytes of the span, or `None` if it's shorter. Unlike `InputTake::take`,
+    /// this can't produce a span running past the end.
+    pub fn try_take(&self, count: usize) -> Option<Self>
+    where
+        T: Clone,
+    {
+        (count <= self.end.saturating_sub(self.start))
+            .then(|| Self::new(self.inner.clone(), self.start, self.start + count))
+    }
+
+    /// How many bytes longer `other` is than this span, negative if it's shorter. After replacing
+    /// this span's text with `other`'s, offsets past it move by this much.
+    pub fn len_delta(&self, other: &Span<T>) -> isize
+    where
+        T: InputLength,
+    {
+        other.input_len() as isize - self.input_len() as isize
+    }
+
+    /// Grows the span in place to end no earlier than `other`. Both spans must share a base.
+    pub fn extend_to(&mut self, other: &Span<T>) {
+        self.end = self.end.max(other.end);
+    }
+
+    /// Replaces the base with `f(base)`, keeping the offsets. `f` must preserve byte positions,
+    /// otherwise the offsets no longer point at the same text.
+    pub fn map_inner<U, F>(self, f: F) -> Span<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Span::new(f(self.inner), self.start, self.end)
+    }
 }
 
 impl<T> From<T> for Span<T>
@@ -85,30 +860,94 @@ where
     }
 }
 
-impl<T> InputLength for Span<T>
-where
-    T: InputLength,
-{
-    fn input_len(&self) -> usize {
-        self.end
-            .min(self.inner.input_len())
-            .saturating_sub(self.start)
+#[cfg(feature = "codespan")]
+fn codespan_offset(offset: usize) -> u32 {
+    u32::try_from(offset)
+        .unwrap_or_else(|_| panic!("span offset {} does not fit in a codespan index", offset))
+}
+
+/// Panics if an offset doesn't fit in a `TextSize`.
+#[cfg(feature = "text-size")]
+impl<T> From<Span<T>> for text_size::TextRange {
+    fn from(span: Span<T>) -> Self {
+        let size = |offset: usize| {
+            text_size::TextSize::try_from(offset)
+                .unwrap_or_else(|_| panic!("span offset {} does not fit in a TextSize", offset))
+        };
+        text_size::TextRange::new(size(span.start), size(span.end))
     }
 }
 
-impl<T> Slice<Range<usize>> for Span<T>
+impl<T> From<&Span<T>> for Span<T>
 where
-    T: Clone,
+    T: Copy,
 {
-    fn slice(&self, range: Range<usize>) -> Self {
-        let start = self.start + range.start;
-        let end = self.start + range.end;
-        Self::new(self.inner.clone(), start, end)
+    fn from(span: &Span<T>) -> Self {
+        *span
     }
 }
 
-impl<T> Slice<RangeFrom<usize>> for Span<T>
-where
+/// `a + b` is the smallest span covering both `a` and `b`, which must share a base.
+impl<T> std::ops::Add for Span<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let start = self.start.min(other.start);
+        let end = self.end.max(other.end);
+        Self::new(self.inner, start, end)
+    }
+}
+
+impl<'a> IntoIterator for Span<&'a str> {
+    type Item = char;
+    type IntoIter = std::str::Chars<'a>;
+
+    /// Iterates the characters of the spanned text.
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_inner().chars()
+    }
+}
+
+impl ExtendInto for Span<&str> {
+    type Item = char;
+    type Extender = String;
+
+    fn new_builder(&self) -> String {
+        String::new()
+    }
+
+    fn extend_into(&self, acc: &mut String) {
+        acc.push_str(self.as_inner());
+    }
+}
+
+impl<T> InputLength for Span<T>
+where
+    T: InputLength,
+{
+    fn input_len(&self) -> usize {
+        self.end
+            .min(self.inner.input_len())
+            .saturating_sub(self.start)
+    }
+}
+
+/// The `Slice` impls add the range to the span's start unchecked, so offsets that overflow a
+/// `usize` panic in debug builds and wrap in release. Use [`Span::checked_slice`] where that
+/// can happen.
+impl<T> Slice<Range<usize>> for Span<T>
+where
+    T: Clone,
+{
+    fn slice(&self, range: Range<usize>) -> Self {
+        let start = self.start + range.start;
+        let end = self.start + range.end;
+        Self::new(self.inner.clone(), start, end)
+    }
+}
+
+impl<T> Slice<RangeFrom<usize>> for Span<T>
+where
     T: Clone,
 {
     fn slice(&self, range: RangeFrom<usize>) -> Self {
@@ -329,4 +1168,971 @@ mod test {
             )),
         );
     }
+
+    #[test]
+    fn test_shifted() {
+        let s = "hello world";
+        let span = Span::new(s, 2, 5);
+
+        assert_eq!(span.shifted(3), Span::new(s, 5, 8));
+        assert_eq!(span.shifted(-2), Span::new(s, 0, 3));
+        assert_eq!(span.shifted(-4), Span::new(s, 0, 1));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let span = Span::new("hello world", 6, 11);
+
+        assert_eq!(span.into_iter().collect::<String>(), "world");
+
+        let mut chars = Vec::new();
+        for c in Span::new("añb", 1, 3) {
+            chars.push(c);
+        }
+        assert_eq!(chars, vec!['ñ']);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let b: &[u8] = b"\x00\x01\x02\x03\x04\x05\x06";
+        let span = Span::new(b, 1, 6);
+
+        assert_eq!(
+            span.chunks(2).collect::<Vec<_>>(),
+            vec![Span::new(b, 1, 3), Span::new(b, 3, 5), Span::new(b, 5, 6)],
+        );
+    }
+
+    #[test]
+    fn test_windows() {
+        let b: &[u8] = b"\x00\x01\x02\x03\x04\x05\x06";
+        let span = Span::new(b, 1, 6);
+
+        assert_eq!(
+            span.windows(3).collect::<Vec<_>>(),
+            vec![Span::new(b, 1, 4), Span::new(b, 2, 5), Span::new(b, 3, 6)],
+        );
+        assert_eq!(span.windows(6).count(), 0);
+    }
+
+    #[test]
+    fn test_map_inner() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Source<'a>(&'a str);
+
+        let s = "hello world";
+        let span = Span::new(s, 6, 11).map_inner(Source);
+
+        assert_eq!(span.inner, Source(s));
+        assert_eq!(span.range(), 6..11);
+    }
+
+    #[test]
+    fn test_str_bytes() {
+        let s = "añb";
+        let span = Span::from_str_bytes(s, 1, 3).unwrap();
+
+        assert_eq!(Span::from_str_bytes(s, 1, 2), None);
+        assert_eq!(Span::from_str_bytes(s, 0, 5), None);
+        assert_eq!(Span::from_str_bytes(s, 3, 1), None);
+        assert_eq!(span.as_bytes_span(), Span::new(s.as_bytes(), 1, 3));
+        assert_eq!(span.as_bytes_span().to_str_span(), Some(span));
+        assert_eq!(Span::new(s.as_bytes(), 1, 2).to_str_span(), None);
+
+        let invalid: &[u8] = b"a\xffb";
+        assert_eq!(Span::new(invalid, 0, 1).to_str_span(), None);
+    }
+
+    #[test]
+    fn test_utf16_line_col() {
+        let s = "a😀b\nx😀y";
+
+        assert_eq!(Span::new(s, 0, 1).utf16_line_col(), (0, 0));
+        assert_eq!(Span::new(s, 5, 6).utf16_line_col(), (0, 3));
+        assert_eq!(Span::new(s, 12, 13).utf16_line_col(), (1, 3));
+    }
+
+    #[test]
+    fn test_extend_to() {
+        let s = "let x = 1;";
+        let tokens = [Span::new(s, 4, 5), Span::new(s, 6, 7), Span::new(s, 8, 9)];
+
+        let mut node = Span::new(s, 0, 3);
+        for token in &tokens {
+            node.extend_to(token);
+        }
+        assert_eq!(node, Span::new(s, 0, 9));
+
+        node.extend_to(&Span::new(s, 1, 2));
+        assert_eq!(node, Span::new(s, 0, 9));
+    }
+
+    #[test]
+    fn test_byte_offset_of_char() {
+        let span = Span::new("xañ€b", 1, 8);
+
+        assert_eq!(span.byte_offset_of_char(0), Some(1));
+        assert_eq!(span.byte_offset_of_char(2), Some(4));
+        assert_eq!(span.byte_offset_of_char(3), Some(7));
+        assert_eq!(span.byte_offset_of_char(4), None);
+    }
+
+    #[test]
+    fn test_full() {
+        let s = "hello";
+
+        assert_eq!(Span::full(s), Span::from(s));
+        assert_eq!(Span::full(s), Span::new(s, 0, 5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path() {
+        use std::path::Path;
+
+        let path = Path::new("/usr/lib/file.rs");
+        let span = Span::from_path(path);
+
+        assert_eq!(span.as_path(), path);
+        assert_eq!(span.slice(5..8).as_path(), Path::new("lib"));
+        assert_eq!(span.slice(9..).as_os_str(), "file.rs");
+    }
+
+    #[test]
+    fn test_line_col() {
+        let s = "ab\nñx\n";
+
+        assert_eq!(Span::new(s, 0, 1).line_col(), (1, 1));
+        assert_eq!(Span::new(s, 2, 3).line_col(), (1, 3));
+        assert_eq!(Span::new(s, 5, 6).line_col(), (2, 2));
+        assert_eq!(Span::new(s, 7, 7).line_col(), (3, 1));
+    }
+
+    #[test]
+    fn test_partition() {
+        let s = "0123456789";
+        let span = |start, end| Span::new(s, start, end);
+
+        assert_eq!(
+            Span::partition(span(0, 5), span(3, 8)),
+            (vec![span(0, 3)], Some(span(3, 5)), vec![span(5, 8)]),
+        );
+        assert_eq!(
+            Span::partition(span(3, 8), span(0, 5)),
+            (vec![span(5, 8)], Some(span(3, 5)), vec![span(0, 3)]),
+        );
+        assert_eq!(
+            Span::partition(span(0, 10), span(3, 5)),
+            (vec![span(0, 3), span(5, 10)], Some(span(3, 5)), vec![]),
+        );
+        assert_eq!(
+            Span::partition(span(3, 5), span(0, 10)),
+            (vec![], Some(span(3, 5)), vec![span(0, 3), span(5, 10)]),
+        );
+        assert_eq!(
+            Span::partition(span(0, 5), span(0, 3)),
+            (vec![span(3, 5)], Some(span(0, 3)), vec![]),
+        );
+        assert_eq!(
+            Span::partition(span(6, 8), span(1, 3)),
+            (vec![span(6, 8)], None, vec![span(1, 3)]),
+        );
+        assert_eq!(
+            Span::partition(span(2, 4), span(4, 4)),
+            (vec![span(2, 4)], None, vec![]),
+        );
+    }
+
+    #[test]
+    fn test_snippet() {
+        let s = "one\ntwo\nthree\nfour\nfive";
+        let span = Span::new(s, 10, 12);
+
+        assert_eq!(span.snippet(0).as_inner(), "three");
+        assert_eq!(span.snippet(1), Span::new(s, 4, 18));
+        assert_eq!(span.snippet(1).as_inner(), "two\nthree\nfour");
+        assert_eq!(span.snippet(5), Span::from(s));
+
+        let span = Span::new(s, 4, 8);
+        assert_eq!(span.snippet(0).as_inner(), "two");
+        assert_eq!(span.snippet(1).as_inner(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_memo_key() {
+        let s = "hello world";
+
+        assert_eq!(
+            Span::new(s, 6, 11).memo_key(),
+            Span::new(s, 6, 8).memo_key()
+        );
+        assert_ne!(
+            Span::new(s, 6, 11).memo_key(),
+            Span::new(s, 5, 11).memo_key()
+        );
+    }
+
+    #[test]
+    fn test_checked_slice() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        assert_eq!(span.checked_slice(1..3), Some(Span::new(s, 7, 9)));
+
+        let span = Span::new(s, usize::MAX - 1, usize::MAX);
+        assert_eq!(span.checked_slice(0..1), Some(span));
+        assert!(span.checked_slice(1..2).is_none());
+        assert!(span.checked_slice(2..2).is_none());
+    }
+
+    #[test]
+    fn test_from_cow() {
+        use std::borrow::Cow;
+
+        let cow: Cow<str> = Cow::Owned("HELLO World".to_lowercase());
+        let span = Span::from_cow(&cow);
+
+        assert_eq!(span.slice(6..).as_inner(), "world");
+        assert_eq!(span.slice(6..).range(), 6..11);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "span start 5 is after end 2")]
+    fn test_new_start_after_end() {
+        Span::new("hello", 5, 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_between_out_of_order() {
+        let s = "hello";
+        Span::between(Span::new(s, 3, 4), Span::new(s, 1, 2));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let s = "hello";
+
+        assert_eq!(Span::try_new(s, 1, 3), Ok(Span::new(s, 1, 3)));
+        assert_eq!(
+            Span::try_new(s, 5, 2),
+            Err(InvalidRange { start: 5, end: 2 })
+        );
+        assert_eq!(
+            InvalidRange { start: 5, end: 2 }.to_string(),
+            "span start 5 is after end 2",
+        );
+    }
+
+    #[test]
+    fn test_match_indices() {
+        let s = "a, b, c, d";
+        let span = Span::new(s, 2, 10);
+
+        assert_eq!(
+            span.match_indices(", ").collect::<Vec<_>>(),
+            vec![Span::new(s, 4, 6), Span::new(s, 7, 9)],
+        );
+    }
+
+    #[test]
+    fn test_streaming_tag() {
+        use nom::bytes::streaming::tag;
+
+        fn hello(s: Span<&str>) -> IResult<Span<&str>, Span<&str>> {
+            tag("hello")(s)
+        }
+
+        let s = "hel";
+        assert_eq!(
+            hello(Span::from(s)),
+            Err(Err::Incomplete(nom::Needed::new(2)))
+        );
+
+        let s = "hello world";
+        assert_eq!(
+            hello(Span::from(s)),
+            Ok((Span::new(s, 5, 11), Span::new(s, 0, 5)))
+        );
+    }
+
+    #[test]
+    fn test_streaming_take_while() {
+        use nom::bytes::streaming::{take_while, take_while1};
+
+        fn digits(s: Span<&str>) -> IResult<Span<&str>, Span<&str>> {
+            take_while(|c: char| c.is_ascii_digit())(s)
+        }
+
+        fn digits1(s: Span<&str>) -> IResult<Span<&str>, Span<&str>> {
+            take_while1(|c: char| c.is_ascii_digit())(s)
+        }
+
+        let s = "x12";
+        assert!(matches!(
+            digits1(Span::new(s, 1, 3)),
+            Err(Err::Incomplete(_))
+        ));
+        assert!(matches!(digits1(Span::new(s, 0, 3)), Err(Err::Error(_))));
+
+        let mut buffer = String::from("let 12");
+        let (rest, _) =
+            tag::<_, _, nom::error::Error<_>>("let ")(Span::from(buffer.as_str())).unwrap();
+        assert!(matches!(digits(rest), Err(Err::Incomplete(_))));
+
+        let start = rest.range().start;
+        buffer.push_str("34;");
+        let rest = Span::new(buffer.as_str(), start, buffer.len());
+        assert_eq!(
+            digits(rest),
+            Ok((
+                Span::new(buffer.as_str(), 8, 9),
+                Span::new(buffer.as_str(), 4, 8)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_char_count() {
+        let span = Span::new("xnaïve", 1, 7);
+
+        assert_eq!(span.input_len(), 6);
+        assert_eq!(span.char_count(), 5);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_grapheme_count() {
+        let span = Span::from("e\u{301}👍🏽");
+
+        assert_eq!(span.char_count(), 4);
+        assert_eq!(span.grapheme_count(), 2);
+    }
+
+    #[cfg(feature = "codespan")]
+    #[test]
+    fn test_codespan() {
+        let span = Span::new("hello world", 6, 11);
+
+        assert_eq!(span.byte_index(), codespan::ByteIndex(6));
+        assert_eq!(span.byte_span(), codespan::Span::new(6, 11));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        let re = regex::Regex::new(r"[0-9]+").unwrap();
+        let s = "7 apples, 12 pears, 300 plums";
+        let span = Span::new(s, 2, 29);
+
+        assert_eq!(span.regex_match(&re), Some(Span::new(s, 10, 12)));
+        assert_eq!(
+            span.regex_matches(&re).collect::<Vec<_>>(),
+            [Span::new(s, 10, 12), Span::new(s, 20, 23)],
+        );
+        assert_eq!(Span::new(s, 1, 9).regex_match(&re), None);
+    }
+
+    #[cfg(feature = "text-size")]
+    #[test]
+    fn test_text_range() {
+        use text_size::{TextRange, TextSize};
+
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+        let range = TextRange::from(span);
+
+        assert_eq!(range, TextRange::new(TextSize::from(6), TextSize::from(11)));
+        assert_eq!(Span::from_text_range(s, range), span);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        assert_eq!(span.truncate(3), Span::new(s, 6, 9));
+        assert_eq!(span.truncate(10), span);
+    }
+
+    #[test]
+    fn test_display_location() {
+        let span = Span::new("fn main() {\n    oops\n}", 16, 20);
+
+        assert_eq!(span.display_location("src/main.rs"), "src/main.rs:2:5");
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let owned = {
+            let source = String::from("let x = 1;");
+            Span::new(source.as_str(), 4, 5).into_owned()
+        };
+
+        assert_eq!(owned.as_str(), "x");
+        assert_eq!(owned.range(), 0..1);
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        let s = "\u{FEFF}hello";
+        assert_eq!(Span::from(s).strip_bom(), Span::new(s, 3, 8));
+
+        let s = "hello";
+        assert_eq!(Span::from(s).strip_bom(), Span::from(s));
+    }
+
+    #[test]
+    fn test_char_indices_spanned() {
+        let s = "xa€b";
+        let chars: Vec<_> = Span::new(s, 1, 6).char_indices_spanned().collect();
+
+        assert_eq!(
+            chars,
+            [
+                ('a', Span::new(s, 1, 2)),
+                ('€', Span::new(s, 2, 5)),
+                ('b', Span::new(s, 5, 6)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_char_spans_rev() {
+        let s = "xa€";
+        let mut chars = Span::new(s, 1, 5).char_spans_rev();
+
+        assert_eq!(chars.next(), Some(Span::new(s, 2, 5)));
+        assert_eq!(chars.next(), Some(Span::new(s, 1, 2)));
+        assert_eq!(chars.next(), None);
+    }
+
+    #[test]
+    fn test_dummy() {
+        assert!(Span::dummy().is_dummy());
+        assert!(!Span::new("hello", 0, 0).is_dummy());
+        assert!(!Span::from("hello").is_dummy());
+        assert!(!Span::from("").is_dummy());
+        assert!(!Span::new(&"x"[..0], 0, 0).is_dummy());
+        assert_eq!(Span::dummy().as_inner(), "");
+    }
+
+    #[test]
+    fn test_lines() {
+        let s = "one\ntwo\nthree";
+
+        assert!(Span::new(s, 4, 7).is_single_line());
+        assert_eq!(Span::new(s, 4, 7).line_count(), 1);
+        assert!(!Span::new(s, 2, 9).is_single_line());
+        assert_eq!(Span::new(s, 2, 9).line_count(), 3);
+    }
+
+    #[test]
+    fn test_display() {
+        let span = Span::new("hello world", 6, 11);
+
+        assert_eq!(format!("{}", span), "world");
+        assert_eq!(format!("{:#}", span), "world at 6..11");
+    }
+
+    #[test]
+    fn test_expanded_column() {
+        let s = "x\n\tab\tc";
+
+        assert_eq!(Span::new(s, 3, 4).expanded_column(4), 5);
+        assert_eq!(Span::new(s, 6, 7).expanded_column(4), 9);
+        assert_eq!(Span::new(s, 6, 7).expanded_column(8), 17);
+        assert_eq!(Span::new(s, 6, 7).line_col(), (2, 5));
+        assert_eq!(Span::new(s, 6, 7).expanded_column(0), 5);
+    }
+
+    #[test]
+    fn test_from_ref() {
+        fn start<'a>(span: impl Into<Span<&'a str>>) -> usize {
+            span.into().range().start
+        }
+
+        let s = "hello world";
+        let spans = [Span::new(s, 0, 5), Span::new(s, 6, 11)];
+
+        assert_eq!(spans.iter().map(start).collect::<Vec<_>>(), vec![0, 6]);
+        assert_eq!(start(spans[1]), 6);
+    }
+
+    #[test]
+    fn test_split_at_absolute() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        assert_eq!(
+            span.split_at_absolute(8),
+            Some((Span::new(s, 6, 8), Span::new(s, 8, 11))),
+        );
+        assert_eq!(
+            span.split_at_absolute(11),
+            Some((span, Span::new(s, 11, 11))),
+        );
+        assert_eq!(span.split_at_absolute(5), None);
+        assert_eq!(span.split_at_absolute(12), None);
+    }
+
+    #[test]
+    fn test_case_spanned() {
+        let s = "SELECT Name";
+        let span = Span::new(s, 7, 11);
+
+        assert_eq!(span.to_lowercase_spanned(), ("name".to_string(), span));
+        assert_eq!(span.to_uppercase_spanned(), ("NAME".to_string(), span));
+    }
+
+    #[test]
+    fn test_leading_trivia() {
+        let s = "let x;\n  \n  y";
+
+        assert_eq!(Span::new(s, 12, 13).leading_trivia(), Span::new(s, 6, 12));
+        assert_eq!(Span::new(s, 5, 6).leading_trivia(), Span::new(s, 5, 5));
+        assert_eq!(Span::new(s, 0, 3).leading_trivia(), Span::new(s, 0, 0));
+    }
+
+    #[test]
+    fn test_count_newlines() {
+        let s = "ab\ncd€\n\n".repeat(10_000) + "end";
+
+        assert_eq!(count_newlines(&s), count_newlines_naive(&s));
+        assert_eq!(count_newlines(&s), 30_000);
+
+        let span = Span::new(s.as_str(), s.len() - 3, s.len());
+        assert_eq!(span.line_col(), (30_001, 1));
+    }
+
+    #[test]
+    fn test_add() {
+        let s = "let x = 1;";
+        let (a, b) = (Span::new(s, 4, 5), Span::new(s, 8, 9));
+
+        assert_eq!(a + b, Span::new(s, 4, 9));
+        assert_eq!(a + b, b + a);
+        assert_eq!(Span::new(s, 0, 10) + a, Span::new(s, 0, 10));
+    }
+
+    #[test]
+    fn test_split_by_lines() {
+        let s = "one\ntwo\nthree";
+
+        assert_eq!(
+            Span::new(s, 1, 11).split_by_lines(),
+            vec![Span::new(s, 1, 3), Span::new(s, 4, 7), Span::new(s, 8, 11)],
+        );
+        assert_eq!(
+            Span::new(s, 4, 8).split_by_lines(),
+            vec![Span::new(s, 4, 7)]
+        );
+        assert_eq!(
+            Span::new(s, 5, 5).split_by_lines(),
+            vec![Span::new(s, 5, 5)]
+        );
+    }
+
+    #[test]
+    fn test_snap_to_char_boundary() {
+        let s = "a€b";
+        let span = Span::new(s, 1, 5);
+
+        assert_eq!(span.snap_to_char_boundary(1), 1);
+        assert_eq!(span.snap_to_char_boundary(2), 1);
+        assert_eq!(span.snap_to_char_boundary(3), 1);
+        assert_eq!(span.snap_to_char_boundary(4), 4);
+        assert_eq!(span.snap_to_char_boundary(0), 1);
+        assert_eq!(span.snap_to_char_boundary(9), 5);
+    }
+
+    /// Asserts that `$parser` treats empty spans the way it treats an empty `&str`, at the
+    /// start, middle and end of a source.
+    macro_rules! assert_empty_like_str {
+        ($parser:expr) => {{
+            let expected: IResult<&str, &str> = $parser("");
+            for span in [
+                Span::from(""),
+                Span::new("abc", 1, 1),
+                Span::new("abc", 3, 3),
+            ] {
+                let actual: IResult<Span<&str>, Span<&str>> = $parser(span);
+                let actual = actual
+                    .map(|(rest, out)| (rest.as_inner(), out.as_inner()))
+                    .map_err(|e| e.map(|e| nom::error::Error::new(e.input.as_inner(), e.code)));
+                assert_eq!(actual, expected, "{:?}", span.range());
+            }
+        }};
+    }
+
+    #[test]
+    fn test_empty_complete() {
+        use nom::bytes::complete::{take_while, take_while1};
+        use nom::character::complete::{alpha0, one_of};
+
+        assert_empty_like_str!(tag("a"));
+        assert_empty_like_str!(tag(""));
+        assert_empty_like_str!(alpha0);
+        assert_empty_like_str!(alpha1);
+        assert_empty_like_str!(take_while(|c: char| c.is_alphabetic()));
+        assert_empty_like_str!(take_while1(|c: char| c.is_alphabetic()));
+        assert_empty_like_str!(nom::combinator::recognize(one_of("abc")));
+    }
+
+    #[test]
+    fn test_empty_streaming() {
+        use nom::bytes::streaming::{tag, take_while, take_while1};
+        use nom::character::streaming::{alpha1, one_of};
+
+        assert_empty_like_str!(tag("a"));
+        assert_empty_like_str!(alpha1);
+        assert_empty_like_str!(take_while(|c: char| c.is_alphabetic()));
+        assert_empty_like_str!(take_while1(|c: char| c.is_alphabetic()));
+        assert_empty_like_str!(nom::combinator::recognize(one_of("abc")));
+    }
+
+    #[test]
+    fn test_to_full_lines() {
+        let s = "one\ntwo\nthree";
+
+        assert_eq!(Span::new(s, 5, 6).to_full_lines(), Span::new(s, 4, 7));
+        assert_eq!(Span::new(s, 2, 10).to_full_lines(), Span::new(s, 0, 13));
+        assert_eq!(Span::new(s, 4, 7).to_full_lines(), Span::new(s, 4, 7));
+        assert_eq!(Span::new(s, 5, 8).to_full_lines(), Span::new(s, 4, 7));
+        assert_eq!(Span::new(s, 8, 8).to_full_lines(), Span::new(s, 8, 13));
+        assert_eq!(
+            Span::new("ab\ncd", 0, 3).to_full_lines(),
+            Span::new("ab\ncd", 0, 2)
+        );
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn test_render_diagnostic() {
+        let s = "let x = 1;\n\nlet y = x +\n    2;\n";
+
+        assert_eq!(
+            Span::new(s, 4, 5).render_diagnostic("main.rs", "error: unused variable"),
+            concat!(
+                "error: unused variable\n",
+                " --> main.rs:1:5\n",
+                "  |\n",
+                "1 | let x = 1;\n",
+                "  |     ^\n",
+            ),
+        );
+        assert_eq!(
+            Span::new(s, 20, 30).render_diagnostic("main.rs", "error: mismatched types"),
+            concat!(
+                "error: mismatched types\n",
+                " --> main.rs:3:9\n",
+                "  |\n",
+                "3 | let y = x +\n",
+                "  |         ^^^\n",
+                "4 |     2;\n",
+                "  | ^^^^^^\n",
+            ),
+        );
+        assert_eq!(
+            Span::new("ab\ncd", 0, 3).render_diagnostic("f", "m"),
+            concat!("m\n", " --> f:1:1\n", "  |\n", "1 | ab\n", "  | ^^\n"),
+        );
+        assert_eq!(
+            Span::new(s, 10, 13).render_diagnostic("main.rs", "error: blank line"),
+            concat!(
+                "error: blank line\n",
+                " --> main.rs:1:11\n",
+                "  |\n",
+                "1 | let x = 1;\n",
+                "  |           ^\n",
+                "2 | \n",
+                "  | ^\n",
+                "3 | let y = x +\n",
+                "  | ^\n",
+            ),
+        );
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn test_highlight_ansi() {
+        let s = "let x = 1;\ny = x + 2;";
+
+        assert_eq!(
+            Span::new(s, 4, 5).highlight_ansi(),
+            "let \x1b[7mx\x1b[0m = 1;\n    ^\n",
+        );
+        assert_eq!(
+            Span::new(s, 8, 12).highlight_ansi(),
+            "let x = \x1b[7m1;\x1b[0m\n        ^^\n\x1b[7my\x1b[0m = x + 2;\n^\n",
+        );
+        assert_eq!(
+            Span::new("ab\ncd", 0, 3).highlight_ansi(),
+            "\x1b[7mab\x1b[0m\n^^\n",
+        );
+    }
+
+    #[test]
+    fn test_split_on() {
+        let s = "(a,,bc,)";
+        let span = Span::new(s, 1, 7);
+
+        assert_eq!(
+            span.split_on(|c| c == ',', false).collect::<Vec<_>>(),
+            [Span::new(s, 1, 2), Span::new(s, 4, 6)],
+        );
+        assert_eq!(
+            span.split_on(|c| c == ',', true).collect::<Vec<_>>(),
+            [
+                Span::new(s, 1, 2),
+                Span::new(s, 3, 3),
+                Span::new(s, 4, 6),
+                Span::new(s, 7, 7),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_eq_ignore_ascii_case() {
+        let span = Span::new("SELECT * FROM t", 0, 6);
+
+        assert!(span.eq_ignore_ascii_case("select"));
+        assert!(span.eq_ignore_ascii_case("SeLeCt"));
+        assert!(!span.eq_ignore_ascii_case("selec"));
+        assert!(!span.eq_ignore_ascii_case("from"));
+    }
+
+    #[test]
+    fn test_end_line_col_inclusive() {
+        let s = "ab\ncdé\n";
+
+        assert_eq!(Span::new(s, 3, 7).end_line_col_inclusive(), (2, 3));
+        assert_eq!(Span::new(s, 7, 7).line_col(), (2, 4));
+        assert_eq!(Span::new(s, 0, 3).end_line_col_inclusive(), (1, 3));
+        assert_eq!(Span::new(s, 4, 4).end_line_col_inclusive(), (2, 2));
+    }
+
+    #[test]
+    fn test_positions() {
+        let s = "hello world";
+        let span = Span::from_positions(s, 6, 11);
+
+        assert_eq!(span, Span::new(s, 6, 11));
+        assert_eq!(span.start_pos()..span.end_pos(), span.range());
+    }
+
+    #[test]
+    fn test_at_line_boundaries() {
+        let s = "ab\ncd";
+
+        assert!(Span::new(s, 0, 1).at_line_start());
+        assert!(Span::new(s, 3, 4).at_line_start());
+        assert!(!Span::new(s, 1, 2).at_line_start());
+        assert!(!Span::new(s, 2, 3).at_line_start());
+
+        assert!(Span::new(s, 1, 2).at_line_end());
+        assert!(Span::new(s, 4, 5).at_line_end());
+        assert!(!Span::new(s, 0, 1).at_line_end());
+        assert!(!Span::new(s, 2, 3).at_line_end());
+    }
+
+    #[test]
+    fn test_split_indent() {
+        let s = "x\n    foo";
+
+        assert_eq!(
+            Span::new(s, 2, 9).split_indent(),
+            (Span::new(s, 2, 6), Span::new(s, 6, 9)),
+        );
+        assert_eq!(
+            Span::new(s, 0, 1).split_indent(),
+            (Span::new(s, 0, 0), Span::new(s, 0, 1)),
+        );
+    }
+
+    #[test]
+    fn test_merge_tokens() {
+        let s = "foo-bar-baz";
+        let tokens = [Span::new(s, 0, 3), Span::new(s, 3, 4), Span::new(s, 4, 7)];
+
+        assert_eq!(
+            Span::merge_tokens(&tokens),
+            Some((Span::new(s, 0, 7), "foo-bar".to_string())),
+        );
+        assert_eq!(
+            Span::merge_tokens(&tokens[2..]),
+            Some((Span::new(s, 4, 7), "bar".to_string())),
+        );
+        assert_eq!(Span::merge_tokens(&[]), None);
+    }
+
+    #[test]
+    fn test_from_substr() {
+        let s = "hello world";
+
+        assert_eq!(Span::from_substr(s, &s[6..]), Some(Span::new(s, 6, 11)));
+        assert_eq!(Span::from_substr(s, &s[11..]), Some(Span::new(s, 11, 11)));
+        assert_eq!(Span::from_substr(&s[..5], &s[6..]), None);
+        assert_eq!(Span::from_substr(&s[6..], &s[..5]), None);
+        let foreign = String::from("world");
+        assert_eq!(Span::from_substr(s, foreign.as_str()), None);
+    }
+
+    #[test]
+    fn test_count_char() {
+        let s = "a,b,c,,d";
+
+        assert_eq!(Span::new(s, 1, 7).count_char(','), 4);
+        assert_eq!(Span::new(s, 2, 5).count_char(','), 1);
+        assert_eq!(Span::new(s, 0, 1).count_char(','), 0);
+    }
+
+    #[test]
+    fn test_try_take() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        assert_eq!(span.try_take(3), Some(Span::new(s, 6, 9)));
+        assert_eq!(span.try_take(5), Some(span));
+        assert_eq!(span.try_take(6), None);
+
+        let reversed = Span {
+            inner: s,
+            start: 8,
+            end: 6,
+        };
+        assert_eq!(reversed.try_take(1), None);
+        assert_eq!(reversed.try_take(0), Some(Span::new(s, 8, 8)));
+    }
+
+    #[test]
+    fn test_expand_to_word() {
+        let s = "let foo_bär = 1;";
+
+        assert_eq!(Span::new(s, 6, 6).expand_to_word(), Span::new(s, 4, 12));
+        assert_eq!(Span::new(s, 5, 8).expand_to_word(), Span::new(s, 4, 12));
+        assert_eq!(Span::new(s, 13, 14).expand_to_word(), Span::new(s, 13, 14));
+        assert_eq!(Span::new(s, 16, 17).expand_to_word(), Span::new(s, 16, 17));
+        assert_eq!(Span::new(s, 12, 15).expand_to_word(), Span::new(s, 12, 15));
+        assert_eq!(Span::new(s, 12, 16).expand_to_word(), Span::new(s, 12, 16));
+        assert_eq!(Span::new(s, 6, 13).expand_to_word(), Span::new(s, 4, 13));
+    }
+
+    #[test]
+    fn test_len_delta() {
+        let s = "let x = foo;";
+        let (short, long) = (Span::new(s, 4, 5), Span::new(s, 8, 11));
+
+        assert_eq!(short.len_delta(&long), 2);
+        assert_eq!(long.len_delta(&short), -2);
+        assert_eq!(long.len_delta(&long), 0);
+    }
+
+    #[test]
+    fn test_content_matches() {
+        let span = Span::new("let x = 1;", 4, 5);
+
+        assert!(span.content_matches("let x = 2;"));
+        assert!(!span.content_matches("let y = 1;"));
+        assert!(!span.content_matches("let"));
+        assert!(!span.content_matches("let é"));
+    }
+
+    #[test]
+    fn test_line_number() {
+        let s = "one\ntwo\n\nfour";
+
+        assert_eq!(Span::new(s, 0, 3).line_number(), 1);
+        assert_eq!(Span::new(s, 3, 4).line_number(), 1);
+        assert_eq!(Span::new(s, 4, 7).line_number(), 2);
+        assert_eq!(Span::new(s, 9, 13).line_number(), 4);
+    }
+
+    #[test]
+    fn test_skip() {
+        let s = "hello world";
+        let span = Span::new(s, 6, 11);
+
+        assert_eq!(span.skip(2), Span::new(s, 8, 11));
+        assert_eq!(span.skip(5), Span::new(s, 11, 11));
+        assert_eq!(span.skip(9), Span::new(s, 11, 11));
+        assert_eq!(span.skip(usize::MAX), Span::new(s, 11, 11));
+    }
+
+    #[test]
+    fn test_replace() {
+        let s = "let x = foo(1);";
+        let callee = Span::new(s, 8, 11);
+
+        let (edited, range) = callee.replace(s, "bar_baz");
+        assert_eq!(edited, "let x = bar_baz(1);");
+        assert_eq!(
+            Span::new(edited.as_str(), range.start, range.end).as_inner(),
+            "bar_baz"
+        );
+        assert_eq!(range, 8..15);
+
+        let (edited, range) = callee.replace(s, "f");
+        assert_eq!(edited, "let x = f(1);");
+        assert_eq!(range, 8..9);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let s = "hello";
+
+        assert!(Span::new(s, 1, 4).is_valid());
+        assert!(Span::new(s, 5, 5).is_valid());
+        assert!(!Span::new(s, 2, 6).is_valid());
+        assert!(!Span::from(s).slice(3..).slice(..4).is_valid());
+        assert!(!Span {
+            inner: s,
+            start: 4,
+            end: 2
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let s = "print println pré prè";
+        let (print, println) = (Span::new(s, 0, 5), Span::new(s, 6, 13));
+
+        assert_eq!(Span::common_prefix(&print, &println), 5);
+        assert_eq!(println.common_prefix_span(&print), Span::new(s, 6, 11));
+
+        let (pre_acute, pre_grave) = (Span::new(s, 14, 18), Span::new(s, 19, 23));
+        assert_eq!(Span::common_prefix(&pre_acute, &pre_grave), 2);
+        assert_eq!(
+            pre_acute.common_prefix_span(&pre_grave),
+            Span::new(s, 14, 16)
+        );
+        assert_eq!(Span::common_prefix(&print, &pre_acute), 2);
+        assert_eq!(Span::common_prefix(&print, &Span::from("private")), 3);
+    }
+
+    #[test]
+    fn test_bits() {
+        use nom::{bits::bits, bits::complete::take, error::Error};
+
+        let b: &[u8] = &[0xff, 0b1010_0000, 0x00];
+        let span = Span::new(b, 1, 3);
+        let (rest, field): (_, u8) =
+            bits::<_, _, Error<(Span<&[u8]>, usize)>, Error<_>, _>(take(3usize))(span).unwrap();
+
+        assert_eq!(field, 0b101);
+        assert_eq!(rest, Span::new(b, 2, 3));
+
+        let (rest, (high, low)): (_, (u8, u16)) = bits::<_, _, Error<_>, Error<_>, _>(
+            nom::sequence::pair(take(3usize), take(7usize)),
+        )(span)
+        .unwrap();
+        assert_eq!((high, low), (0b101, 0b0000000));
+        assert_eq!(rest, Span::new(b, 3, 3));
+
+        let (bit_rest, _): (_, u8) = take::<_, _, _, Error<_>>(3usize)((span, 0)).unwrap();
+        assert_eq!(bit_rest, (Span::new(b, 1, 3), 3));
+    }
 }
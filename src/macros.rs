@@ -0,0 +1,97 @@
+/// Defines a parser function whose result is paired with the span it consumed, saving the
+/// usual `let start = input; ...; Span::between(start, input)` dance. The body is written as
+/// for a plain parser returning the declared type; the defined function returns the span and
+/// that output. The function may take lifetime parameters but not type parameters.
+///
+/// ```
+/// use nom::{bytes::complete::tag, character::complete::{alpha1, digit1}, IResult};
+/// use span::{spanned_parser, Span};
+///
+/// spanned_parser! {
+///     fn assignment<'a>(input: Span<&'a str>) -> IResult<Span<&'a str>, (Span<&'a str>, i64)> {
+///         let (input, name) = alpha1(input)?;
+///         let (input, _) = tag(" = ")(input)?;
+///         let (input, value) = digit1(input)?;
+///         Ok((input, (name, value.value_i64())))
+///     }
+/// }
+///
+/// let s = "x = 42;";
+/// let (_, (span, (name, value))) = assignment(Span::from(s)).unwrap();
+/// assert_eq!((span, name, value), (Span::new(s, 0, 6), Span::new(s, 0, 1), 42));
+/// ```
+#[macro_export]
+macro_rules! spanned_parser {
+    (
+        $(#[$attr:meta])*
+        $vis:vis fn $name:ident $(<$($lt:lifetime),*>)? ($input:ident : $in:ty)
+            -> $res:ident<$i:ty, $o:ty $(, $e:ty)?>
+        $body:block
+    ) => {
+        $(#[$attr])*
+        $vis fn $name $(<$($lt),*>)? ($input: $in) -> $res<$i, ($i, $o) $(, $e)?> {
+            let start = $input.clone();
+            let body = |$input: $in| -> $res<$i, $o $(, $e)?> { $body };
+            let (rest, output) = body($input)?;
+            Ok((rest.clone(), ($crate::Span::between(start, rest), output)))
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{error::SpanError, Span};
+    use nom::{
+        bytes::complete::tag,
+        character::complete::{alpha1, digit1},
+        error::ErrorKind,
+        Err, IResult,
+    };
+
+    crate::spanned_parser! {
+        fn pair<'a>(input: Span<&'a str>) -> IResult<Span<&'a str>, Span<&'a str>> {
+            let (input, _) = alpha1(input)?;
+            let (input, _) = tag(":")(input)?;
+            digit1(input)
+        }
+    }
+
+    crate::spanned_parser! {
+        /// Parses a keyword.
+        pub(crate) fn keyword<'a>(
+            input: Span<&'a str>
+        ) -> IResult<Span<&'a str>, (), SpanError<&'a str>> {
+            let (input, _) = tag("let")(input)?;
+            Ok((input, ()))
+        }
+    }
+
+    #[test]
+    fn test_spanned_parser() {
+        let s = "(ab:12)";
+
+        assert_eq!(
+            pair(Span::new(s, 1, 7)),
+            Ok((Span::new(s, 6, 7), (Span::new(s, 1, 6), Span::new(s, 4, 6)))),
+        );
+    }
+
+    #[test]
+    fn test_spanned_parser_error() {
+        let s = "let x";
+
+        assert_eq!(
+            keyword(Span::from(s)),
+            Ok((Span::new(s, 3, 5), (Span::new(s, 0, 3), ()))),
+        );
+        assert_eq!(
+            keyword(Span::new(s, 4, 5)),
+            Err(Err::Error(SpanError {
+                span: Span::new(s, 4, 5),
+                kind: ErrorKind::Tag,
+                stack: Vec::new(),
+                context: Vec::new(),
+            })),
+        );
+    }
+}
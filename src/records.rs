@@ -0,0 +1,52 @@
+//! Delimiter-separated records, one per line, such as CSV without quoting.
+
+use crate::{combinator::take_line, Span};
+use nom::{error::ParseError, IResult, Slice};
+
+/// Parses one line into the spans of its `delimiter`-separated fields. Empty fields come back
+/// as empty spans at their position in the line.
+pub fn parse_record<'a, E>(
+    delimiter: char,
+) -> impl FnMut(Span<&'a str>) -> IResult<Span<&'a str>, Vec<Span<&'a str>>, E>
+where
+    E: ParseError<Span<&'a str>>,
+{
+    move |input: Span<&'a str>| {
+        let (rest, line) = take_line(input)?;
+        let mut fields = Vec::new();
+        let mut start = 0;
+        for (i, _) in line.as_inner().match_indices(delimiter) {
+            fields.push(line.slice(start..i));
+            start = i + delimiter.len_utf8();
+        }
+        fields.push(line.slice(start..));
+        Ok((rest, fields))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::error::Error;
+
+    #[test]
+    fn test_parse_record() {
+        let s = "a,,c\nd;e\n";
+        let mut parse = parse_record::<Error<_>>(',');
+
+        assert_eq!(
+            parse(Span::from(s)),
+            Ok((
+                Span::new(s, 5, 9),
+                vec![Span::new(s, 0, 1), Span::new(s, 2, 2), Span::new(s, 3, 4)],
+            )),
+        );
+        assert_eq!(
+            parse_record::<Error<_>>(';')(Span::new(s, 5, 9)),
+            Ok((
+                Span::new(s, 9, 9),
+                vec![Span::new(s, 5, 6), Span::new(s, 7, 8)]
+            )),
+        );
+    }
+}
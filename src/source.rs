@@ -0,0 +1,57 @@
+//! Sources whose text can be read back by byte range without being sliceable into `Self`, such
+//! as ropes.
+
+use std::{borrow::Cow, ops::Range};
+
+/// A source that can give the text of a byte range, borrowed where it's stored contiguously.
+pub trait SpanSource {
+    /// The text of `range`. Panics if it's out of bounds or not on char boundaries.
+    fn text(&self, range: Range<usize>) -> Cow<'_, str>;
+}
+
+impl SpanSource for str {
+    fn text(&self, range: Range<usize>) -> Cow<'_, str> {
+        Cow::Borrowed(&self[range])
+    }
+}
+
+impl SpanSource for String {
+    fn text(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.as_str().text(range)
+    }
+}
+
+#[cfg(feature = "ropey")]
+impl SpanSource for ropey::Rope {
+    fn text(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.byte_slice(range).into()
+    }
+}
+
+impl<S: SpanSource + ?Sized> SpanSource for &S {
+    fn text(&self, range: Range<usize>) -> Cow<'_, str> {
+        (**self).text(range)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Span;
+
+    #[test]
+    fn test_str_source() {
+        let span = Span::new("hello world", 6, 11);
+
+        assert_eq!(span.text(), "world");
+        assert!(matches!(span.text(), std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[cfg(feature = "ropey")]
+    #[test]
+    fn test_rope_source() {
+        let rope = ropey::Rope::from_str("hello wörld");
+        let span = Span::new(&rope, 6, 12);
+
+        assert_eq!(span.text(), "wörld");
+    }
+}
@@ -0,0 +1,146 @@
+//! Sets of disjoint regions of a source, such as scattered highlights.
+
+use crate::Span;
+use std::ops::Range;
+
+/// Sorted, non-overlapping spans over one source. Overlapping or touching spans are coalesced as
+/// they're inserted.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct SpanSet<T> {
+    spans: Vec<Span<T>>,
+}
+
+impl<T> std::fmt::Debug for SpanSet<T>
+where
+    T: std::fmt::Debug + nom::Slice<Range<usize>>,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_set().entries(&self.spans).finish()
+    }
+}
+
+impl<T> Default for SpanSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SpanSet<T> {
+    pub fn new() -> Self {
+        Self { spans: Vec::new() }
+    }
+
+    /// The spans in order of position.
+    pub fn as_slice(&self) -> &[Span<T>] {
+        &self.spans
+    }
+
+    /// Adds `span`, merging it with any spans it overlaps or touches. Empty spans cover nothing,
+    /// so they're ignored.
+    pub fn insert(&mut self, span: Span<T>) {
+        if span.start == span.end {
+            return;
+        }
+        let lo = self.spans.partition_point(|s| s.end < span.start);
+        let hi = self.spans.partition_point(|s| s.start <= span.end);
+        let (mut start, mut end) = (span.start, span.end);
+        if lo < hi {
+            start = start.min(self.spans[lo].start);
+            end = end.max(self.spans[hi - 1].end);
+        }
+        self.spans
+            .splice(lo..hi, [Span::new(span.inner, start, end)]);
+    }
+
+    /// Whether any span in the set contains the byte at `offset`.
+    pub fn contains(&self, offset: usize) -> bool {
+        let i = self.spans.partition_point(|s| s.end <= offset);
+        self.spans.get(i).is_some_and(|s| s.start <= offset)
+    }
+
+    /// Merges neighbouring spans separated by at most `max_gap` bytes, absorbing the gaps.
+    pub fn merge_adjacent(&mut self, max_gap: usize) {
+        let mut merged: Vec<Span<T>> = Vec::with_capacity(self.spans.len());
+        for span in self.spans.drain(..) {
+            match merged.last_mut() {
+                Some(last) if span.start - last.end <= max_gap => last.end = span.end,
+                _ => merged.push(span),
+            }
+        }
+        self.spans = merged;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let s = "0123456789";
+        let mut set = SpanSet::new();
+        set.insert(Span::new(s, 6, 8));
+        set.insert(Span::new(s, 1, 2));
+        set.insert(Span::new(s, 4, 5));
+
+        assert_eq!(
+            set.as_slice(),
+            [Span::new(s, 1, 2), Span::new(s, 4, 5), Span::new(s, 6, 8)],
+        );
+    }
+
+    #[test]
+    fn test_insert_coalesces() {
+        let s = "0123456789";
+        let mut set = SpanSet::new();
+        set.insert(Span::new(s, 1, 2));
+        set.insert(Span::new(s, 4, 5));
+        set.insert(Span::new(s, 7, 9));
+
+        set.insert(Span::new(s, 2, 4));
+        assert_eq!(set.as_slice(), [Span::new(s, 1, 5), Span::new(s, 7, 9)]);
+
+        set.insert(Span::new(s, 3, 8));
+        assert_eq!(set.as_slice(), [Span::new(s, 1, 9)]);
+    }
+
+    #[test]
+    fn test_insert_empty() {
+        let s = "0123456789";
+        let mut set = SpanSet::new();
+        set.insert(Span::new(s, 3, 3));
+        assert_eq!(set.as_slice(), []);
+
+        set.insert(Span::new(s, 1, 3));
+        set.insert(Span::new(s, 3, 3));
+        set.insert(Span::new(s, 6, 6));
+        assert_eq!(set.as_slice(), [Span::new(s, 1, 3)]);
+        assert!(!set.contains(6));
+    }
+
+    #[test]
+    fn test_contains() {
+        let s = "0123456789";
+        let mut set = SpanSet::new();
+        set.insert(Span::new(s, 1, 3));
+        set.insert(Span::new(s, 6, 7));
+
+        let contained: Vec<_> = (0..10).filter(|&i| set.contains(i)).collect();
+        assert_eq!(contained, [1, 2, 6]);
+    }
+
+    #[test]
+    fn test_merge_adjacent() {
+        let s = "0123456789";
+        let mut set = SpanSet::new();
+        set.insert(Span::new(s, 0, 1));
+        set.insert(Span::new(s, 2, 3));
+        set.insert(Span::new(s, 6, 7));
+
+        set.merge_adjacent(1);
+        assert_eq!(set.as_slice(), [Span::new(s, 0, 3), Span::new(s, 6, 7)]);
+
+        set.merge_adjacent(3);
+        assert_eq!(set.as_slice(), [Span::new(s, 0, 7)]);
+    }
+}
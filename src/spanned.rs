@@ -0,0 +1,146 @@
+//! Spans paired with arbitrary metadata.
+
+use crate::Span;
+use std::ops::Range;
+
+/// A span carrying some metadata `meta`, such as a token kind or file id.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Spanned<M, T> {
+    pub span: Span<T>,
+    pub meta: M,
+}
+
+impl<M, T> std::fmt::Debug for Spanned<M, T>
+where
+    M: std::fmt::Debug,
+    T: std::fmt::Debug + nom::Slice<Range<usize>>,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Spanned")
+            .field("span", &self.span)
+            .field("meta", &self.meta)
+            .finish()
+    }
+}
+
+impl<M, T> Spanned<M, T> {
+    pub fn new(span: Span<T>, meta: M) -> Self {
+        Self { span, meta }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.span.range()
+    }
+
+    /// Extends the span to the end of `other`, keeping this value's metadata.
+    pub fn to(self, other: Span<T>) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(Span::to(self.span, other), self.meta)
+    }
+
+    /// Extends the span to the start of `other`, keeping this value's metadata.
+    pub fn between(self, other: Span<T>) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(Span::between(self.span, other), self.meta)
+    }
+
+    pub fn map_meta<N, F>(self, f: F) -> Spanned<N, T>
+    where
+        F: FnOnce(M) -> N,
+    {
+        Spanned::new(self.span, f(self.meta))
+    }
+}
+
+/// Values that know the span of source they came from.
+pub trait HasSpan<T> {
+    fn span(&self) -> Span<T>;
+}
+
+impl<T: Clone> HasSpan<T> for Span<T> {
+    fn span(&self) -> Span<T> {
+        self.clone()
+    }
+}
+
+impl<M, T: Clone> HasSpan<T> for Spanned<M, T> {
+    fn span(&self) -> Span<T> {
+        self.span.clone()
+    }
+}
+
+/// A tuple spans from the start of its first element to the end of its last, so the elements in
+/// between don't need to be spanned at all.
+macro_rules! impl_has_span_for_tuple {
+    ($first:ident $(, $mid:ident)*; $last:ident) => {
+        impl<T: Clone, $first: HasSpan<T>, $($mid,)* $last: HasSpan<T>> HasSpan<T>
+            for ($first, $($mid,)* $last)
+        {
+            fn span(&self) -> Span<T> {
+                let (first, .., last) = self;
+                Span::to(first.span(), last.span())
+            }
+        }
+    };
+}
+
+impl_has_span_for_tuple!(A; B);
+impl_has_span_for_tuple!(A, B; C);
+impl_has_span_for_tuple!(A, B, C; D);
+impl_has_span_for_tuple!(A, B, C, D; E);
+impl_has_span_for_tuple!(A, B, C, D, E; F);
+
+/// The span covering a tuple of parser outputs, from its first element to its last.
+pub fn join_spanned_tuple<T, U: HasSpan<T>>(tuple: &U) -> Span<T> {
+    tuple.span()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Kind {
+        Ident,
+        Number,
+    }
+
+    #[test]
+    fn test_to() {
+        let s = "foo 42";
+        let ident = Spanned::new(Span::new(s, 0, 3), Kind::Ident);
+        let number = Spanned::new(Span::new(s, 4, 6), Kind::Number);
+
+        let joined = ident.to(number.span);
+        assert_eq!(joined, Spanned::new(Span::new(s, 0, 6), Kind::Ident));
+        assert_eq!(joined.range(), 0..6);
+
+        let gap = ident.between(number.span);
+        assert_eq!(gap, Spanned::new(Span::new(s, 0, 4), Kind::Ident));
+    }
+
+    #[test]
+    fn test_map_meta() {
+        let s = "foo";
+        let ident = Spanned::new(Span::from(s), Kind::Ident);
+
+        assert_eq!(
+            ident.map_meta(|kind| kind == Kind::Ident),
+            Spanned::new(Span::from(s), true),
+        );
+    }
+
+    #[test]
+    fn test_join_spanned_tuple() {
+        let s = "let x = 42";
+        let ident = Spanned::new(Span::new(s, 4, 5), Kind::Ident);
+        let tuple = (ident, "=", Spanned::new(Span::new(s, 8, 10), Kind::Number));
+
+        assert_eq!(join_spanned_tuple(&tuple), Span::new(s, 4, 10));
+        assert_eq!((Span::new(s, 0, 3), ident).span(), Span::new(s, 0, 5));
+    }
+}